@@ -0,0 +1,148 @@
+//! Benchmarks for each stage of the pipeline.
+//!
+//! Decoding and resizing are measured for a few sizes of the source image, and
+//! the stages after resizing are measured for a few widths of the art.
+
+use charcoal::AsciiArtGenerator;
+use criterion::{
+    criterion_group, criterion_main, BatchSize, Bencher, BenchmarkId, Criterion, Throughput,
+};
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+use std::io::Cursor;
+
+const SOURCE_SIZES: &[(u32, u32)] = &[(320, 240), (1280, 960), (4000, 3000)];
+// Source for the stages after resizing.
+const ART_SOURCE_SIZE: (u32, u32) = (1280, 960);
+const ART_WIDTHS: &[u32] = &[80, 200, 500];
+
+/// Gradients with some noise and edges, so that it's somewhat like a photo
+/// (for the decoder and the filters).
+fn source_image((width, height): (u32, u32)) -> DynamicImage {
+    let mut seed = 0x2545_f491_u32;
+    let img = RgbImage::from_fn(width, height, |x, y| {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let noise = seed % 32;
+        let (fx, fy) = (x * 255 / width, y * 255 / height);
+        let stripe = if (x / 64 + y / 64) % 2 == 0 { 0 } else { 64 };
+        Rgb([
+            ((fx + noise) % 256) as u8,
+            ((fy + stripe) % 256) as u8,
+            ((fx + fy) / 2 + noise).min(255) as u8,
+        ])
+    });
+
+    DynamicImage::ImageRgb8(img)
+}
+
+fn encode_png(img: &DynamicImage) -> Vec<u8> {
+    let mut bytes = vec![];
+    img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .expect("encoding PNG");
+    bytes
+}
+
+fn size_id((width, height): (u32, u32)) -> BenchmarkId {
+    BenchmarkId::from_parameter(format!("{}x{}", width, height))
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+    for &size in SOURCE_SIZES {
+        let bytes = encode_png(&source_image(size));
+        group.throughput(Throughput::Elements(size.0 as u64 * size.1 as u64));
+        group.bench_with_input(size_id(size), &bytes, |b, bytes| {
+            b.iter(|| AsciiArtGenerator::from_bytes(bytes).expect("decoding"))
+        });
+    }
+}
+
+fn resize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resize");
+    group.sample_size(10);
+    for &size in SOURCE_SIZES {
+        let gen = AsciiArtGenerator::from_image(source_image(size)).expect("loading image");
+        group.throughput(Throughput::Elements(size.0 as u64 * size.1 as u64));
+        group.bench_with_input(size_id(size), &gen, |b, gen| {
+            b.iter(|| gen.processor().resize())
+        });
+    }
+}
+
+/// Output of the stages for the resized image.
+struct Stages {
+    img: DynamicImage,
+    fg: DynamicImage,
+    blended: DynamicImage,
+}
+
+/// Benchmarks the given stage after resizing the image (for each art width).
+/// Each width gets its own generator, so the stage can change its settings.
+fn bench_after_resize<F>(c: &mut Criterion, name: &str, mut bench: F)
+where
+    F: FnMut(&mut Bencher, &AsciiArtGenerator, &Stages),
+{
+    let mut group = c.benchmark_group(name);
+    let source = source_image(ART_SOURCE_SIZE);
+    for &width in ART_WIDTHS {
+        let mut gen = AsciiArtGenerator::from_image(source.clone()).expect("loading image");
+        let (cols, rows) = gen.set_width(width).expect("resizing");
+        let stages = {
+            let proc = gen.processor();
+            let img = proc.resize();
+            let fg = proc.blur_and_invert(&img);
+            let blended = proc.blend_and_adjust(&img, &fg);
+            Stages { img, fg, blended }
+        };
+
+        group.throughput(Throughput::Elements(cols as u64 * rows as u64));
+        group.bench_function(size_id((cols, rows)), |b| bench(b, &gen, &stages));
+    }
+}
+
+fn blur_and_invert(c: &mut Criterion) {
+    bench_after_resize(c, "blur_and_invert", |b, gen, stages| {
+        b.iter(|| gen.processor().blur_and_invert(&stages.img))
+    });
+}
+
+fn blur_and_invert_fast(c: &mut Criterion) {
+    bench_after_resize(c, "blur_and_invert_fast", |b, gen, stages| {
+        gen.fast_blur.set(true);
+        b.iter(|| gen.processor().blur_and_invert(&stages.img))
+    });
+}
+
+fn blend_and_adjust_levels(c: &mut Criterion) {
+    bench_after_resize(c, "blend_and_adjust_levels", |b, gen, stages| {
+        let fg = stages.fg.to_rgb8();
+        b.iter_batched_ref(
+            || stages.img.to_rgb8(),
+            |img| gen.processor().blend_and_adjust_levels(img, &fg),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn generate_from_img(c: &mut Criterion) {
+    bench_after_resize(c, "generate_from_img", |b, gen, stages| {
+        b.iter(|| {
+            let proc = gen.processor();
+            proc.generate_from_img(&stages.blended).for_each(drop)
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    decode,
+    resize,
+    blur_and_invert,
+    blur_and_invert_fast,
+    blend_and_adjust_levels,
+    generate_from_img
+);
+criterion_main!(benches);
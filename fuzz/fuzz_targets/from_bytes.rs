@@ -0,0 +1,13 @@
+//! Decodes arbitrary bytes (like uploads from users) with all the formats enabled.
+
+#![no_main]
+
+use charcoal::AsciiArtGenerator;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(gen) = AsciiArtGenerator::from_bytes(data) {
+        gen.grid_size();
+        gen.params();
+    }
+});
@@ -0,0 +1,167 @@
+//! Decodes arbitrary bytes and runs the image through each stage of the pipeline
+//! with arbitrary options.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use charcoal::{AsciiArtGenerator, Crop, FilterType, SizeLimits, SizePolicy, Transform};
+use image::Rgb;
+use libfuzzer_sys::fuzz_target;
+
+// Larger images (or sizes) only make the fuzzer slower.
+const MAX_PIXELS: u64 = 1 << 20;
+const MAX_SIZE: u16 = 1024;
+const MAX_TRANSFORMS: usize = 4;
+
+const FILTERS: &[FilterType] = &[
+    FilterType::Nearest,
+    FilterType::Triangle,
+    FilterType::CatmullRom,
+    FilterType::Gaussian,
+    FilterType::Lanczos3,
+];
+
+#[derive(Arbitrary, Debug)]
+enum Size {
+    Width(u16),
+    Height(u16),
+    Cols(u16),
+    Rows(u16),
+    Fit(u16, u16),
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzTransform {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Rotate { degrees: f32, fill: [u8; 3] },
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl From<FuzzTransform> for Transform {
+    fn from(t: FuzzTransform) -> Self {
+        match t {
+            FuzzTransform::Rotate90 => Transform::Rotate90,
+            FuzzTransform::Rotate180 => Transform::Rotate180,
+            FuzzTransform::Rotate270 => Transform::Rotate270,
+            FuzzTransform::Rotate { degrees, fill } => Transform::Rotate {
+                degrees,
+                fill: Rgb(fill),
+            },
+            FuzzTransform::FlipHorizontal => Transform::FlipHorizontal,
+            FuzzTransform::FlipVertical => Transform::FlipVertical,
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct Limits {
+    max_source_width: Option<u32>,
+    max_source_height: Option<u32>,
+    max_source_pixels: Option<u64>,
+    max_cols: Option<u16>,
+    max_rows: Option<u16>,
+    reject: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    min_level: u8,
+    max_level: u8,
+    gamma: f32,
+    filter: u8,
+    upscale_filter: u8,
+    fast_resize: bool,
+    fast_blur: bool,
+    limits: Option<Limits>,
+    crop: Option<(u32, u32, u32, u32)>,
+    transforms: Vec<FuzzTransform>,
+    size: Option<Size>,
+    ramp: Option<String>,
+    image: &'a [u8],
+}
+
+fuzz_target!(|input: Input| {
+    let mut gen = match AsciiArtGenerator::from_bytes(input.image) {
+        Ok(gen) => gen,
+        Err(_) => return,
+    };
+
+    let params = gen.params();
+    if params.source_width as u64 * params.source_height as u64 > MAX_PIXELS {
+        return;
+    }
+
+    gen.min_level.set(input.min_level);
+    gen.max_level.set(input.max_level);
+    gen.gamma.set(input.gamma);
+    gen.filter
+        .set(FILTERS[input.filter as usize % FILTERS.len()]);
+    gen.upscale_filter
+        .set(FILTERS[input.upscale_filter as usize % FILTERS.len()]);
+    gen.fast_resize.set(input.fast_resize);
+    gen.fast_blur.set(input.fast_blur);
+
+    if let Some(l) = input.limits {
+        let _ = gen.set_size_limits(SizeLimits {
+            max_source_width: l.max_source_width,
+            max_source_height: l.max_source_height,
+            max_source_pixels: l.max_source_pixels,
+            max_cols: l.max_cols.map(u32::from),
+            max_rows: l.max_rows.map(u32::from),
+            policy: if l.reject {
+                SizePolicy::Reject
+            } else {
+                SizePolicy::Clamp
+            },
+        });
+    }
+
+    if let Some((x, y, width, height)) = input.crop {
+        // Invalid rectangles should be rejected (without panicking).
+        let _ = gen.set_crop(Some(Crop {
+            x,
+            y,
+            width,
+            height,
+        }));
+    }
+
+    // Same for transforms which leave the image empty (or too large).
+    let _ = gen.set_transforms(
+        input
+            .transforms
+            .into_iter()
+            .take(MAX_TRANSFORMS)
+            .map(Transform::from)
+            .collect(),
+    );
+
+    // Sizes are either adjusted or rejected (which leaves the size as it was).
+    let size = match input.size {
+        Some(Size::Width(w)) => gen.set_width((w % MAX_SIZE) as u32),
+        Some(Size::Height(h)) => gen.set_height((h % MAX_SIZE) as u32),
+        Some(Size::Cols(c)) => gen.set_cols((c % MAX_SIZE) as u32),
+        Some(Size::Rows(r)) => gen.set_rows((r % MAX_SIZE) as u32),
+        Some(Size::Fit(c, r)) => gen.fit((c % MAX_SIZE) as u32, (r % MAX_SIZE) as u32),
+        None => Ok(gen.grid_size()),
+    };
+    let (cols, rows) = size.unwrap_or_else(|_| gen.grid_size());
+    assert!(cols > 0 && rows > 0);
+
+    if let Some(ramp) = input.ramp {
+        let _ = gen.set_ramp(&ramp);
+    }
+
+    let proc = gen.processor();
+    let img = proc.resize();
+    assert_eq!((img.width(), img.height()), (cols, rows));
+
+    let fg = proc.blur_and_invert(&img);
+    let final_img = proc.blend_and_adjust(&img, &fg);
+    let art: Vec<_> = proc.generate_from_img(&final_img).collect();
+    assert_eq!(art.len(), rows as usize);
+    assert!(art.iter().all(|row| row.chars().count() == cols as usize));
+});
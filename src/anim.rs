@@ -0,0 +1,259 @@
+use crate::art::{
+    ArtStream, AsciiArt, AsciiArtGenerator, Crop, DEFAULT_GAMMA, DEFAULT_MAX_LEVEL,
+    DEFAULT_MIN_LEVEL,
+};
+use crate::error::Error;
+use crate::limits::SizeLimits;
+use crate::transform::Transform;
+use image::codecs::png::PngDecoder;
+use image::error::{LimitError, LimitErrorKind};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, Frame, Frames, ImageError, ImageFormat, Limits};
+
+use std::cell::Cell;
+use std::io::Cursor;
+
+// Like browsers, delays of 10ms or less (including missing ones) are bumped to 100ms.
+const MAX_IGNORED_DELAY_MS: u32 = 10;
+const FALLBACK_FRAME_DELAY_MS: u32 = 100;
+
+/// A frame of the generated art, along with how long it should be shown.
+pub struct AsciiArtFrame {
+    pub art: AsciiArt,
+    pub delay_ms: u32,
+}
+
+/// Image decoded from a buffer (see [`AsciiArtAnimation::decode`]).
+pub enum Decoded {
+    Still(AsciiArtGenerator),
+    Animated(AsciiArtAnimation),
+}
+
+/// Generator for animated images (GIF and APNG). Every frame goes through the
+/// same pipeline as a still image, using the level settings of the animation.
+pub struct AsciiArtAnimation {
+    pub min_level: Cell<u8>,
+    pub max_level: Cell<u8>,
+    pub gamma: Cell<f32>,
+    pub filter: Cell<FilterType>,
+    pub upscale_filter: Cell<FilterType>,
+    pub fast_resize: Cell<bool>,
+    pub fast_blur: Cell<bool>,
+    frames: Vec<(AsciiArtGenerator, u32)>,
+}
+
+impl AsciiArtAnimation {
+    /// Decodes all the frames from the given buffer. Still images are treated
+    /// as animations with a single frame.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes_with_limits(bytes, Limits::no_limits(), usize::MAX)
+    }
+
+    /// Same as [`Self::from_bytes`], but constrained by the given limits (see
+    /// [`Self::decode`]).
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: Limits,
+        max_frames: usize,
+    ) -> Result<Self, Error> {
+        match Self::decode(bytes, limits, max_frames)? {
+            Decoded::Still(gen) => Ok(Self::with_frames(vec![(gen, FALLBACK_FRAME_DELAY_MS)])),
+            Decoded::Animated(anim) => Ok(anim),
+        }
+    }
+
+    /// Decodes the image in the given buffer (in one go), which is animated if
+    /// it's a GIF or APNG with more than one frame.
+    ///
+    /// The decoder is constrained by the given limits (for untrusted inputs), and
+    /// since all frames are kept around, their total size is limited by the same
+    /// allocation limit, and their number by the given maximum.
+    pub fn decode(bytes: &[u8], limits: Limits, max_frames: usize) -> Result<Decoded, Error> {
+        let mut frames = match image::guess_format(bytes) {
+            #[cfg(feature = "gif")]
+            Ok(ImageFormat::Gif) => {
+                use image::ImageDecoder;
+
+                let mut decoder = image::codecs::gif::GifDecoder::new(Cursor::new(bytes))?;
+                decoder.set_limits(limits.clone())?;
+                Self::collect_frames(decoder.into_frames(), &limits, max_frames)?
+            }
+            Ok(ImageFormat::Png) => {
+                let decoder = PngDecoder::with_limits(Cursor::new(bytes), limits.clone())?;
+                if decoder.is_apng()? {
+                    Self::collect_frames(decoder.apng()?.into_frames(), &limits, max_frames)?
+                } else {
+                    vec![]
+                }
+            }
+            _ => vec![],
+        };
+
+        match frames.pop() {
+            None => AsciiArtGenerator::from_bytes_with_limits(bytes, limits).map(Decoded::Still),
+            Some((gen, _)) if frames.is_empty() => Ok(Decoded::Still(gen)),
+            Some(last) => {
+                frames.push(last);
+                Ok(Decoded::Animated(Self::with_frames(frames)))
+            }
+        }
+    }
+
+    fn with_frames(frames: Vec<(AsciiArtGenerator, u32)>) -> Self {
+        AsciiArtAnimation {
+            min_level: Cell::new(DEFAULT_MIN_LEVEL),
+            max_level: Cell::new(DEFAULT_MAX_LEVEL),
+            gamma: Cell::new(DEFAULT_GAMMA),
+            filter: Cell::new(FilterType::Lanczos3),
+            upscale_filter: Cell::new(FilterType::Lanczos3),
+            fast_resize: Cell::new(true),
+            fast_blur: Cell::new(false),
+            frames,
+        }
+    }
+
+    /// Number of frames in this animation.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether this animation has no frames (which shouldn't happen).
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Sets the size limits of all frames and returns the grid size.
+    /// See [`AsciiArtGenerator::set_size_limits`] for details.
+    pub fn set_size_limits(&mut self, limits: SizeLimits) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.set_size_limits(limits))
+    }
+
+    /// Sets the width of all frames and returns the grid size of the art.
+    /// See [`AsciiArtGenerator::set_width`] for details.
+    pub fn set_width(&mut self, width: u32) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.set_width(width))
+    }
+
+    /// Sets the number of columns of all frames and returns the grid size.
+    /// See [`AsciiArtGenerator::set_cols`] for details.
+    pub fn set_cols(&mut self, cols: u32) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.set_cols(cols))
+    }
+
+    /// Sets the number of rows of all frames and returns the grid size.
+    /// See [`AsciiArtGenerator::set_rows`] for details.
+    pub fn set_rows(&mut self, rows: u32) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.set_rows(rows))
+    }
+
+    /// Fits all frames within the given columns and rows, and returns the
+    /// actual grid size. See [`AsciiArtGenerator::fit`] for details.
+    pub fn fit(&mut self, cols: u32, rows: u32) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.fit(cols, rows))
+    }
+
+    /// Crops all frames to the given rectangle and returns the grid size.
+    /// See [`AsciiArtGenerator::set_crop`] for details.
+    pub fn set_crop(&mut self, crop: Option<Crop>) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.set_crop(crop))
+    }
+
+    /// Sets the transforms of all frames and returns the grid size.
+    /// See [`AsciiArtGenerator::set_transforms`] for details.
+    pub fn set_transforms(&mut self, transforms: Vec<Transform>) -> Result<(u32, u32), Error> {
+        self.resize_frames(|gen| gen.set_transforms(transforms.clone()))
+    }
+
+    /// Sets the characters used for all frames.
+    /// See [`AsciiArtGenerator::set_ramp`] for details.
+    pub fn set_ramp(&mut self, ramp: &str) -> Result<(), Error> {
+        for (gen, _) in &mut self.frames {
+            gen.set_ramp(ramp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs each frame through the pipeline and returns the frames lazily
+    /// (as and when they're generated).
+    pub fn generate(&self) -> impl Iterator<Item = AsciiArtFrame> + '_ {
+        self.frames.iter().map(move |(gen, delay)| AsciiArtFrame {
+            art: self.apply_settings(gen).processor().generate(),
+            delay_ms: *delay,
+        })
+    }
+
+    /// Streams the rows of each frame (see [`Processor::stream`](crate::art::Processor::stream)),
+    /// along with the delay of that frame.
+    pub fn stream(&self) -> impl Iterator<Item = (ArtStream<'_>, u32)> + '_ {
+        self.frames
+            .iter()
+            .map(move |(gen, delay)| (self.apply_settings(gen).processor().stream(), *delay))
+    }
+
+    /// Copies the settings of the animation to the generator of a frame.
+    fn apply_settings<'a>(&self, gen: &'a AsciiArtGenerator) -> &'a AsciiArtGenerator {
+        gen.min_level.set(self.min_level.get());
+        gen.max_level.set(self.max_level.get());
+        gen.gamma.set(self.gamma.get());
+        gen.filter.set(self.filter.get());
+        gen.upscale_filter.set(self.upscale_filter.get());
+        gen.fast_resize.set(self.fast_resize.get());
+        gen.fast_blur.set(self.fast_blur.get());
+        gen
+    }
+
+    // All frames have the same dimensions, so they end up with the same grid size
+    // (and errors show up in the first frame, before anything's changed).
+    fn resize_frames<F>(&mut self, resize: F) -> Result<(u32, u32), Error>
+    where
+        F: Fn(&mut AsciiArtGenerator) -> Result<(u32, u32), Error>,
+    {
+        let mut size = (0, 0);
+        for (gen, _) in &mut self.frames {
+            size = resize(gen)?;
+        }
+
+        Ok(size)
+    }
+
+    // Converts the frames as they're decoded, as long as they're within the limits.
+    fn collect_frames(
+        frames: Frames,
+        limits: &Limits,
+        max_frames: usize,
+    ) -> Result<Vec<(AsciiArtGenerator, u32)>, Error> {
+        let mut budget = limits.max_alloc;
+        let mut converted = vec![];
+        for frame in frames {
+            let frame = frame?;
+            if converted.len() == max_frames {
+                return Err(Error::FrameCount(max_frames));
+            }
+
+            if let Some(b) = &mut budget {
+                let size = frame.buffer().as_raw().len() as u64;
+                *b = b.checked_sub(size).ok_or_else(|| {
+                    ImageError::Limits(LimitError::from_kind(LimitErrorKind::InsufficientMemory))
+                })?;
+            }
+
+            converted.push(Self::convert_frame(frame)?);
+        }
+
+        Ok(converted)
+    }
+
+    fn convert_frame(frame: Frame) -> Result<(AsciiArtGenerator, u32), Error> {
+        let (n, d) = frame.delay().numer_denom_ms();
+        let delay = match n.checked_div(d) {
+            Some(ms) if ms > MAX_IGNORED_DELAY_MS => ms,
+            _ => FALLBACK_FRAME_DELAY_MS,
+        };
+
+        let img = DynamicImage::ImageRgba8(frame.into_buffer());
+        Ok((AsciiArtGenerator::from_image(img)?, delay))
+    }
+}
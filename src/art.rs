@@ -1,24 +1,67 @@
+use crate::blur;
+use crate::error::Error;
+use crate::levels::{self, Levels};
+use crate::limits::{SizeLimits, SizePolicy};
+use crate::transform::Transform;
 use crate::utils;
-use image::{DynamicImage, GenericImageView, ImageError, RgbImage};
+use image::imageops::FilterType;
+use image::{
+    DynamicImage, GenericImageView, GrayImage, ImageFormat, ImageReader, Limits, RgbImage,
+    RgbaImage,
+};
 
+use std::borrow::Cow;
 use std::cell::Cell;
 use std::cmp;
+use std::io::{self, Cursor, Write};
 use std::ops::Deref;
+use std::str::FromStr;
 
-const BLEND_RATIO: f32 = 0.5;
 const MAX_WIDTH: u32 = 500;
+// Images larger than these many times the target size are box-sampled down
+// to this size before the final resize.
+const FAST_RESIZE_FACTOR: u32 = 3;
+// Standard deviation of the blur (for the foreground).
+const BLUR_SIGMA: f32 = 8.0;
+// Rows of the resized image in each band of the streamed art, and the rows around
+// them which are blurred along with them (the Gaussian kernel reaches up to 3 sigma,
+// rounded up, and the box blurs don't reach as far).
+pub(crate) const STREAM_BAND_ROWS: u32 = 64;
+const STREAM_MARGIN_ROWS: u32 = 3 * BLUR_SIGMA as u32 + 1;
 
 pub const DEFAULT_MIN_LEVEL: u8 = 78;
 pub const DEFAULT_MAX_LEVEL: u8 = 125;
 pub const DEFAULT_GAMMA: f32 = 0.78;
 
+/// Image formats that can be decoded with the enabled features.
+pub const SUPPORTED_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    #[cfg(feature = "bmp")]
+    ImageFormat::Bmp,
+    #[cfg(feature = "gif")]
+    ImageFormat::Gif,
+    #[cfg(feature = "ico")]
+    ImageFormat::Ico,
+    #[cfg(feature = "pnm")]
+    ImageFormat::Pnm,
+    #[cfg(feature = "qoi")]
+    ImageFormat::Qoi,
+    #[cfg(feature = "tga")]
+    ImageFormat::Tga,
+    #[cfg(feature = "tiff")]
+    ImageFormat::Tiff,
+    #[cfg(feature = "webp")]
+    ImageFormat::WebP,
+];
+
 /* Constants below are obtained using Python. See https://github.com/wafflespeanut/ascii-art-generator/blob/0b519b00b43eadb8500db30c304b2b87ad7eb159/src/gen.py#L27-L39 */
 
 // Char width and height based on system fonts.
-const DEFAULT_CHAR_WIDTH: f32 = 6.0;
-const DEFAULT_CHAR_HEIGHT: f32 = 11.0;
+pub(crate) const DEFAULT_CHAR_WIDTH: f32 = 6.0;
+pub(crate) const DEFAULT_CHAR_HEIGHT: f32 = 11.0;
 // Characters sorted based on the pixel density of their render.
-const CHARS: &[char] = &[
+pub(crate) const CHARS: &[char] = &[
     'H', '$', 'd', 'g', 'q', '0', 'p', 'R', '8', 'b', 'h', 'k', 'B', 'D', 'N', 'Q', 'U', '5', '6',
     '9', '@', 'A', 'K', 'y', 'E', 'G', 'O', 'Z', '2', '4', '#', 'a', 'f', 'u', 'M', 'P', 'S', '3',
     '%', 'l', 't', 'x', 'W', 'X', 'Y', '1', '&', 'j', 'n', 's', 'z', 'C', '7', 'e', 'i', 'm', 'o',
@@ -27,11 +70,106 @@ const CHARS: &[char] = &[
     ' ',
 ];
 
+/// The generated art, along with the stuff needed for exporting it.
+pub struct AsciiArt {
+    /// Rows of characters.
+    pub rows: Vec<String>,
+    /// Colour of each character, sampled from the resized image.
+    pub colors: RgbImage,
+    /// Luma of each character (after blending and adjusting levels).
+    pub luma: GrayImage,
+    /// Parameters used for generating this art.
+    pub params: ArtParams,
+}
+
+/// Parameters of the generator (for including in exports).
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ArtParams {
+    /// Dimensions of the source image.
+    pub source_width: u32,
+    pub source_height: u32,
+    /// Number of columns and rows in the art.
+    pub cols: u32,
+    pub rows: u32,
+    pub min_level: u8,
+    pub max_level: u8,
+    pub gamma: f32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_filter"))]
+    pub filter: FilterType,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_filter"))]
+    pub upscale_filter: FilterType,
+    pub fast_resize: bool,
+    pub fast_blur: bool,
+    pub crop: Option<Crop>,
+    pub transforms: Vec<Transform>,
+    /// Characters used (from densest to lightest).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub ramp: String,
+}
+
+/// Serializes the filter by its name (`image` doesn't implement `Serialize` for it).
+#[cfg(feature = "serde")]
+fn serialize_filter<S: serde::Serializer>(filter: &FilterType, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(match filter {
+        FilterType::Nearest => "nearest",
+        FilterType::Triangle => "triangle",
+        FilterType::CatmullRom => "catmull-rom",
+        FilterType::Gaussian => "gaussian",
+        FilterType::Lanczos3 => "lanczos",
+    })
+}
+
+/// Rectangle (in source pixels) for cropping the image before generating the art.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Crop {
+    type Err = ();
+
+    /// Parses the rectangle from `x,y,width,height`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut values = s.split(',').map(|v| v.trim().parse().map_err(|_| ()));
+        let crop = Crop {
+            x: values.next().ok_or(())??,
+            y: values.next().ok_or(())??,
+            width: values.next().ok_or(())??,
+            height: values.next().ok_or(())??,
+        };
+
+        match values.next() {
+            Some(_) => Err(()),
+            None => Ok(crop),
+        }
+    }
+}
+
 /// This project - the whole deal.
 pub struct AsciiArtGenerator {
     pub min_level: Cell<u8>,
     pub max_level: Cell<u8>,
     pub gamma: Cell<f32>,
+    /// Filter used when the image is scaled down.
+    pub filter: Cell<FilterType>,
+    /// Filter used when the image is scaled up (nearest neighbour works well
+    /// for pixel art, while Lanczos is better for photos).
+    pub upscale_filter: Cell<FilterType>,
+    /// Whether large images should be box-sampled first (which is much faster,
+    /// but slightly different from resizing with the filter all the way).
+    pub fast_resize: Cell<bool>,
+    /// Whether the Gaussian blur should be approximated with box blurs (which
+    /// is much faster, but slightly changes the art). Off by default.
+    pub fast_blur: Cell<bool>,
+    crop: Option<Crop>,
+    transforms: Vec<Transform>,
+    ramp: Vec<char>,
+    limits: SizeLimits,
     width: u32,
     height: u32,
     img: DynamicImage,
@@ -40,64 +178,286 @@ pub struct AsciiArtGenerator {
 
 impl AsciiArtGenerator {
     /// Creates an instance from the given buffer.
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImageError> {
-        let img = image::load_from_memory(bytes)?;
-        let (w, h) = (img.width(), img.height());
-        let clamped_width = cmp::min(w, MAX_WIDTH);
+    ///
+    /// The format is guessed from the contents, and the buffer is rejected early
+    /// if the relevant decoder hasn't been enabled.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let format = image::guess_format(bytes).map_err(|_| Error::UnsupportedFormat(None))?;
+        if !SUPPORTED_FORMATS.contains(&format) {
+            return Err(Error::UnsupportedFormat(Some(format)));
+        }
+
+        let img = image::load_from_memory_with_format(bytes, format)?;
+        Self::from_image(img)
+    }
+
+    /// Same as [`Self::from_bytes`], but the decoder is constrained by the given
+    /// limits (for untrusted inputs, like decompression bombs).
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: Limits) -> Result<Self, Error> {
+        let format = image::guess_format(bytes).map_err(|_| Error::UnsupportedFormat(None))?;
+        if !SUPPORTED_FORMATS.contains(&format) {
+            return Err(Error::UnsupportedFormat(Some(format)));
+        }
+
+        let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+        reader.limits(limits);
+        Self::from_image(reader.decode()?)
+    }
+
+    /// Creates an instance from RGBA pixels (4 bytes per pixel, row-major),
+    /// like the ones from canvas `ImageData`.
+    pub fn from_rgba8(width: u32, height: u32, pixels: &[u8]) -> Result<Self, Error> {
+        RgbaImage::from_raw(width, height, pixels.to_vec())
+            .ok_or(Error::BufferSize(width, height, pixels.len()))
+            .and_then(|img| Self::from_image(DynamicImage::ImageRgba8(img)))
+    }
 
+    /// Creates an instance from RGB pixels (3 bytes per pixel, row-major).
+    pub fn from_rgb8(width: u32, height: u32, pixels: &[u8]) -> Result<Self, Error> {
+        RgbImage::from_raw(width, height, pixels.to_vec())
+            .ok_or(Error::BufferSize(width, height, pixels.len()))
+            .and_then(|img| Self::from_image(DynamicImage::ImageRgb8(img)))
+    }
+
+    /// Creates an instance from grayscale pixels (1 byte per pixel, row-major).
+    pub fn from_luma8(width: u32, height: u32, pixels: &[u8]) -> Result<Self, Error> {
+        GrayImage::from_raw(width, height, pixels.to_vec())
+            .ok_or(Error::BufferSize(width, height, pixels.len()))
+            .and_then(|img| Self::from_image(DynamicImage::ImageLuma8(img)))
+    }
+
+    /// Creates an instance from an already decoded image. Empty images (with
+    /// zero width or height) are rejected.
+    pub fn from_image(img: DynamicImage) -> Result<Self, Error> {
         let mut gen = AsciiArtGenerator {
             min_level: Cell::new(DEFAULT_MIN_LEVEL),
             max_level: Cell::new(DEFAULT_MAX_LEVEL),
             gamma: Cell::new(DEFAULT_GAMMA),
+            filter: Cell::new(FilterType::Lanczos3),
+            upscale_filter: Cell::new(FilterType::Lanczos3),
+            fast_resize: Cell::new(true),
+            fast_blur: Cell::new(false),
 
+            crop: None,
+            transforms: vec![],
+            ramp: CHARS.to_vec(),
+            limits: SizeLimits::default(),
             img,
-            width: w,
-            height: h,
-            ar: w as f32 / h as f32,
+            width: 0,
+            height: 0,
+            ar: 1.0,
         };
 
-        if clamped_width < w {
-            gen.set_width(MAX_WIDTH);
+        gen.reset_size(None, vec![])?;
+        Ok(gen)
+    }
+
+    /// Sets the limits for the dimensions of the source image and the art, and
+    /// returns the grid size of the art (adjusted for the new limits based on
+    /// their policy).
+    ///
+    /// **NOTE:** Nothing's changed if the (cropped and transformed) image doesn't
+    /// fit within the new limits, or if the art doesn't and the policy is
+    /// [`SizePolicy::Reject`].
+    pub fn set_size_limits(&mut self, limits: SizeLimits) -> Result<(u32, u32), Error> {
+        let (w, h) = self.source_size(self.crop, &self.transforms);
+        if !limits.allows_source((w, h)) {
+            return Err(Error::ImageSize(w, h));
         }
 
-        Ok(gen)
+        let (width, height) =
+            limit_size(&limits, limits.policy, self.ar, (self.width, self.height))?;
+        self.limits = limits;
+        self.width = width;
+        self.height = height;
+        Ok(self.grid_size())
+    }
+
+    /// Returns the limits for the dimensions of the source image and the art.
+    #[inline]
+    pub fn size_limits(&self) -> SizeLimits {
+        self.limits
     }
 
-    /// Sets the width of the final image and returns the new height.
+    /// Sets the width of the final image and returns the grid size (columns
+    /// and rows) of the art.
     ///
     /// **NOTE:**
-    /// - No-op if the width is greater than the actual width of the image.
+    /// - Images are upscaled if the width is greater than the actual width of
+    ///   the image (using [`Self::upscale_filter`]), so the limits (see
+    ///   [`Self::set_size_limits`]) should be set for untrusted widths.
     /// - This also affects the height to maintain aspect ratio.
     /// - This only stores the dimensions - scaling is done while generating the art.
     /// - The image will be resized once again to match character widths and heights,
     /// but will be closer to this value.
-    pub fn set_width(&mut self, width: u32) -> u32 {
-        if width >= self.img.width() {
-            return self.height;
-        }
-
-        self.width = width;
-        self.height = (width as f32 / self.ar) as u32;
-        self.height
+    /// - The size is adjusted (or rejected) if the art ends up empty or larger
+    ///   than the limits (see [`Self::set_size_limits`]).
+    pub fn set_width(&mut self, width: u32) -> Result<(u32, u32), Error> {
+        self.set_size((width, (width as f32 / self.ar) as u32))
     }
 
-    /// Sets the height of the final image and returns the new width.
+    /// Sets the height of the final image and returns the grid size (columns
+    /// and rows) of the art.
     ///
     /// **NOTE:**
-    /// - No-op if the height is greater than the actual height of the image.
+    /// - Images are upscaled if the height is greater than the actual height of
+    ///   the image (using [`Self::upscale_filter`]).
     /// - This also affects the width to maintain aspect ratio.
     /// - This only stores the dimensions - scaling is done while generating the art.
     /// - The height of the image will probably change later to fit the character
     /// widths and heights.
-    pub fn set_height(&mut self, height: u32) -> u32 {
-        let actual = self.img.height();
-        if height >= actual {
-            return self.width;
+    /// - Like [`Self::set_width`], the size is subject to the limits.
+    pub fn set_height(&mut self, height: u32) -> Result<(u32, u32), Error> {
+        self.set_size(((height as f32 * self.ar) as u32, height))
+    }
+
+    /// Sets the number of columns in the art and returns the grid size.
+    ///
+    /// Same as [`Self::set_width`], since each pixel of the final image is a character.
+    #[inline]
+    pub fn set_cols(&mut self, cols: u32) -> Result<(u32, u32), Error> {
+        self.set_width(cols)
+    }
+
+    /// Sets the number of rows in the art and returns the grid size.
+    pub fn set_rows(&mut self, rows: u32) -> Result<(u32, u32), Error> {
+        self.set_height(height_for_rows(rows))
+    }
+
+    /// Scales the art to the largest size that fits within the given columns
+    /// and rows (maintaining aspect ratio) and returns the actual grid size.
+    pub fn fit(&mut self, cols: u32, rows: u32) -> Result<(u32, u32), Error> {
+        let height = (cols as f32 / self.ar) as u32;
+        if rows_for_height(height) > rows {
+            return self.set_rows(rows);
+        }
+
+        self.set_size((cols, height))
+    }
+
+    /// Crops the image to the given rectangle (or removes the crop) before
+    /// generating the art, and returns the grid size of the art.
+    ///
+    /// **NOTE:** This resets the size of the final image (based on the new aspect
+    /// ratio), so this should be called before setting the width or height.
+    pub fn set_crop(&mut self, crop: Option<Crop>) -> Result<(u32, u32), Error> {
+        let (w, h) = (self.img.width(), self.img.height());
+        match crop {
+            Some(c) if c.width == 0 || c.height == 0 => return Err(Error::InvalidCrop(c)),
+            Some(c) if c.x.saturating_add(c.width) > w || c.y.saturating_add(c.height) > h => {
+                return Err(Error::InvalidCrop(c))
+            }
+            _ => (),
+        }
+
+        self.reset_size(crop, self.transforms.clone())
+    }
+
+    /// Returns the crop rectangle (if any).
+    #[inline]
+    pub fn crop(&self) -> Option<Crop> {
+        self.crop
+    }
+
+    /// Sets the transforms (applied in order, after cropping) and returns the grid
+    /// size of the art. Transforms which leave the image empty (like rotating by
+    /// `NaN` degrees) or too large for the limits are rejected.
+    ///
+    /// **NOTE:** Like [`Self::set_crop`], this resets the size of the final image.
+    pub fn set_transforms(&mut self, transforms: Vec<Transform>) -> Result<(u32, u32), Error> {
+        self.reset_size(self.crop, transforms)
+    }
+
+    /// Returns the transforms applied to the image.
+    #[inline]
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+
+    // Dimensions of the source image with the given crop and transforms.
+    fn source_size(&self, crop: Option<Crop>, transforms: &[Transform]) -> (u32, u32) {
+        let size = match crop {
+            Some(c) => (c.width, c.height),
+            None => (self.img.width(), self.img.height()),
+        };
+
+        transforms.iter().fold(size, |size, t| t.size(size))
+    }
+
+    // Sets the crop and transforms (if the image is within the limits), and
+    // resets the dimensions to those of the resulting image (clamped to the
+    // maximum width and the limits, like new images).
+    fn reset_size(
+        &mut self,
+        crop: Option<Crop>,
+        transforms: Vec<Transform>,
+    ) -> Result<(u32, u32), Error> {
+        let (w, h) = self.source_size(crop, &transforms);
+        if !self.limits.allows_source((w, h)) {
+            return Err(Error::ImageSize(w, h));
         }
 
+        let ar = w as f32 / h as f32;
+        let size = if w > MAX_WIDTH {
+            (MAX_WIDTH, (MAX_WIDTH as f32 / ar) as u32)
+        } else {
+            (w, h)
+        };
+
+        // This isn't something that the caller asked for, so it's always clamped.
+        let (width, height) = limit_size(&self.limits, SizePolicy::Clamp, ar, size)?;
+        self.crop = crop;
+        self.transforms = transforms;
+        self.ar = ar;
+        self.width = width;
+        self.height = height;
+        Ok(self.grid_size())
+    }
+
+    // Sets the dimensions of the final image (if they're within the limits).
+    fn set_size(&mut self, size: (u32, u32)) -> Result<(u32, u32), Error> {
+        let (width, height) = limit_size(&self.limits, self.limits.policy, self.ar, size)?;
+        self.width = width;
         self.height = height;
-        self.width = (height as f32 * self.ar) as u32;
-        self.width
+        Ok(self.grid_size())
+    }
+
+    /// Returns the number of columns and rows in the art (for the current dimensions).
+    #[inline]
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.width, rows_for_height(self.height))
+    }
+
+    /// Sets the characters used for the art, ordered from the densest to the
+    /// lightest. The default ramp is based on the pixel density of the characters.
+    pub fn set_ramp(&mut self, ramp: &str) -> Result<(), Error> {
+        if ramp.is_empty() {
+            return Err(Error::EmptyRamp);
+        }
+
+        self.ramp = ramp.chars().collect();
+        Ok(())
+    }
+
+    /// Returns the current parameters of this generator.
+    pub fn params(&self) -> ArtParams {
+        let (cols, rows) = self.grid_size();
+        ArtParams {
+            source_width: self.img.width(),
+            source_height: self.img.height(),
+            cols,
+            rows,
+            min_level: self.min_level.get(),
+            max_level: self.max_level.get(),
+            gamma: self.gamma.get(),
+            filter: self.filter.get(),
+            upscale_filter: self.upscale_filter.get(),
+            fast_resize: self.fast_resize.get(),
+            fast_blur: self.fast_blur.get(),
+            crop: self.crop,
+            transforms: self.transforms.clone(),
+            ramp: self.ramp.iter().collect(),
+        }
     }
 
     /// Return the processor which takes care of generating the artwork.
@@ -120,19 +480,54 @@ impl<'a> Deref for Processor<'a> {
 }
 
 impl<'a> Processor<'a> {
+    /// Returns the source image for the art (after cropping and transforming, if needed).
+    pub fn source(&self) -> Cow<'_, DynamicImage> {
+        let img = match self.crop {
+            Some(c) => Cow::Owned(self.img.crop_imm(c.x, c.y, c.width, c.height)),
+            None => Cow::Borrowed(&self.img),
+        };
+
+        self.transforms
+            .iter()
+            .fold(img, |img, t| Cow::Owned(t.apply(&img)))
+    }
+
     /// Returns the resized image with corrections to the specified dimensions.
     #[inline]
     pub fn resize(&self) -> DynamicImage {
-        let h = (self.height as f32 * DEFAULT_CHAR_WIDTH / DEFAULT_CHAR_HEIGHT) as u32;
-        self.img
-            .resize_exact(self.width, h, image::imageops::Lanczos3)
+        let img = self.source();
+        let (w, h) = self.grid_size();
+        let (src_w, src_h) = (img.width(), img.height());
+        if w > src_w || h > src_h {
+            return img.resize_exact(w, h, self.upscale_filter.get());
+        }
+
+        let (box_w, box_h) = (
+            w.saturating_mul(FAST_RESIZE_FACTOR),
+            h.saturating_mul(FAST_RESIZE_FACTOR),
+        );
+        if self.fast_resize.get() && src_w > box_w && src_h > box_h {
+            // Filters go through a lot of source pixels for each target pixel
+            // when scaling down by large factors, whereas box sampling is cheap.
+            return img
+                .thumbnail_exact(box_w, box_h)
+                .resize_exact(w, h, self.filter.get());
+        }
+
+        img.resize_exact(w, h, self.filter.get())
     }
 
-    /// Applies Guassian blur and inverts the image. This will be blended
-    /// with the original image and adjusted for levels.
+    /// Applies Guassian blur (or its approximation, see [`AsciiArtGenerator::fast_blur`])
+    /// and inverts the image. This will be blended with the original image and
+    /// adjusted for levels.
     #[inline]
     pub fn blur_and_invert(&self, img: &DynamicImage) -> DynamicImage {
-        let mut img = img.blur(8.0);
+        let mut img = if self.fast_blur.get() {
+            blur::box_blur(img, BLUR_SIGMA)
+        } else {
+            img.blur(BLUR_SIGMA)
+        };
+
         img.invert();
         img
     }
@@ -143,60 +538,169 @@ impl<'a> Processor<'a> {
         let fg_buf = fg.to_rgb8();
         self.blend_and_adjust_levels(&mut actual_buf, &fg_buf);
 
-        let detailed = DynamicImage::ImageRgb8(actual_buf);
-        DynamicImage::ImageLuma8(detailed.to_luma8())
+        let mut luma = GrayImage::new(actual_buf.width(), actual_buf.height());
+        utils::zip_pixels_mut(&mut luma, 1, &actual_buf, 3, levels::rgb_to_luma);
+        DynamicImage::ImageLuma8(luma)
+    }
+
+    /// Runs the whole pipeline and returns the art.
+    pub fn generate(&self) -> AsciiArt {
+        let img = self.resize();
+        let fg = self.blur_and_invert(&img);
+        let final_img = self.blend_and_adjust(&img, &fg);
+        AsciiArt {
+            rows: self.generate_from_img(&final_img).collect(),
+            colors: img.to_rgb8(),
+            luma: final_img.to_luma8(),
+            params: self.params(),
+        }
+    }
+
+    /// Runs the pipeline a band of rows at a time, and returns the rows as they're
+    /// generated. This gives the same rows as [`Self::generate`], but only the
+    /// resized image (and a band's worth of the other stages) is kept around.
+    pub fn stream(&self) -> ArtStream<'a> {
+        ArtStream {
+            gen: self.0,
+            img: self.resize(),
+            next_row: 0,
+            band: Vec::new().into_iter(),
+        }
     }
 
     /// Converts the image to Luma, maps the characters and returns a `String` iterator.
     pub fn generate_from_img(&'a self, img: &'a DynamicImage) -> impl Iterator<Item = String> + 'a {
-        let multiplier = (CHARS.len() - 1) as f32;
+        let ramp = &self.ramp;
+        let multiplier = (ramp.len() - 1) as f32;
         let (width, height) = (img.width(), img.height());
-        (0..height).map(move |y| {
+        utils::map_rows(height, move |y| {
             (0..width)
                 .map(|x| {
                     let p = img.get_pixel(x, y).0[0] as f32 / 255.0;
-                    CHARS[(p * multiplier + 0.5) as usize]
+                    ramp[(p * multiplier + 0.5) as usize]
                 })
                 .collect()
         })
     }
 
-    fn blend_and_adjust_levels(&self, actual_buf: &mut RgbImage, fg_buf: &RgbImage) {
-        let (min, max, inv_gamma) = (
-            self.min_level.get() as f32 / 255.0,
-            self.max_level.get() as f32 / 255.0,
-            1.0 / self.gamma.get(),
+    /// Blends the (blurred and inverted) foreground into the image in place,
+    /// and adjusts the levels. This is the bulk of [`Self::blend_and_adjust`].
+    pub fn blend_and_adjust_levels(&self, actual_buf: &mut RgbImage, fg_buf: &RgbImage) {
+        let levels = Levels::new(self.min_level.get(), self.max_level.get(), self.gamma.get());
+        utils::zip_pixels_mut(actual_buf, 3, fg_buf, 3, |buf, fg| levels.apply(buf, fg));
+    }
+}
+
+/// Rows of the art, generated a band at a time (see [`Processor::stream`]).
+pub struct ArtStream<'a> {
+    gen: &'a AsciiArtGenerator,
+    img: DynamicImage,
+    next_row: u32,
+    band: std::vec::IntoIter<String>,
+}
+
+impl ArtStream<'_> {
+    /// Returns the resized image (which has the colours of the characters).
+    pub fn image(&self) -> &DynamicImage {
+        &self.img
+    }
+
+    /// Generates the rows of the next band (if any). Each band is blurred along
+    /// with the rows around it, so that it's the same as blurring the whole image.
+    pub fn next_band(&mut self) -> Option<Vec<String>> {
+        let (width, height) = (self.img.width(), self.img.height());
+        if self.next_row >= height {
+            return None;
+        }
+
+        let (start, end) = (
+            self.next_row,
+            cmp::min(self.next_row + STREAM_BAND_ROWS, height),
+        );
+        let (top, bottom) = (
+            start.saturating_sub(STREAM_MARGIN_ROWS),
+            cmp::min(end + STREAM_MARGIN_ROWS, height),
         );
 
-        actual_buf
-            .pixels_mut()
-            .zip(fg_buf.pixels())
-            .for_each(|(p1, p2)| {
-                let r = blend_pixel(p1[0], p2[0], BLEND_RATIO);
-                let g = blend_pixel(p1[1], p2[1], BLEND_RATIO);
-                let b = blend_pixel(p1[2], p2[2], BLEND_RATIO);
-
-                let (h, s, mut v) = utils::convert_rgb_to_hsv((r, g, b));
-                if v <= min {
-                    v = 0.0;
-                } else if v >= max {
-                    v = 1.0;
-                } else {
-                    v = ((v - min) / (max - min)).powf(inv_gamma);
-                }
-
-                let (r, g, b) = utils::convert_hsv_to_rgb((h, s, v));
-                p1.0 = [
-                    (r * 255.0).round() as u8,
-                    (g * 255.0).round() as u8,
-                    (b * 255.0).round() as u8,
-                ];
-            });
+        let proc = self.gen.processor();
+        let fg = proc.blur_and_invert(&self.img.crop_imm(0, top, width, bottom - top));
+        let fg = fg.crop_imm(0, start - top, width, end - start);
+        let band = self.img.crop_imm(0, start, width, end - start);
+        let final_img = proc.blend_and_adjust(&band, &fg);
+
+        self.next_row = end;
+        Some(proc.generate_from_img(&final_img).collect())
+    }
+
+    /// Writes the rows (each followed by a newline) as they're generated.
+    pub fn write_to<W: Write>(self, mut writer: W) -> io::Result<()> {
+        for row in self {
+            writeln!(writer, "{}", row)?;
+        }
+
+        writer.flush()
+    }
+}
+
+impl Iterator for ArtStream<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if let Some(row) = self.band.next() {
+            return Some(row);
+        }
+
+        self.band = self.next_band()?.into_iter();
+        self.band.next()
     }
 }
 
-/// Blends a pixel value using the given ratio and returns the normalized value in [0, 1]
+/// Number of rows for the given height (in pixels), since characters are
+/// taller than they're wide.
 #[inline]
-const fn blend_pixel(p1: u8, p2: u8, ratio: f32) -> f32 {
-    (p1 as f32 * (1.0 - ratio) + p2 as f32 * ratio) / 255.0
+fn rows_for_height(height: u32) -> u32 {
+    (height as f32 * DEFAULT_CHAR_WIDTH / DEFAULT_CHAR_HEIGHT) as u32
+}
+
+/// Smallest height (in pixels) which gives us the given rows.
+fn height_for_rows(rows: u32) -> u32 {
+    let mut height = (rows as f32 * DEFAULT_CHAR_HEIGHT / DEFAULT_CHAR_WIDTH) as u32;
+    while rows_for_height(height) < rows && height < u32::MAX {
+        height += 1;
+    }
+
+    height
+}
+
+/// Adjusts the dimensions (in pixels) of the final image (with the given aspect
+/// ratio), so that the art is within the limits.
+fn limit_size(
+    limits: &SizeLimits,
+    policy: SizePolicy,
+    ar: f32,
+    (mut width, mut height): (u32, u32),
+) -> Result<(u32, u32), Error> {
+    let (cols, rows) = (width, rows_for_height(height));
+    if limits.allows_grid((cols, rows)) {
+        return Ok((width, height));
+    } else if policy == SizePolicy::Reject {
+        return Err(Error::ArtSize(cols, rows));
+    }
+
+    // Scale down to fit within the maximum size (maintaining aspect ratio),
+    // and then stretch it to at least one column and row.
+    if let Some(c) = limits.max_cols.filter(|&c| width > c) {
+        width = c;
+        height = (c as f32 / ar) as u32;
+    }
+
+    if let Some(r) = limits.max_rows.filter(|&r| rows_for_height(height) > r) {
+        height = height_for_rows(r);
+        width = cmp::min(
+            (height as f32 * ar) as u32,
+            limits.max_cols.unwrap_or(u32::MAX),
+        );
+    }
+
+    Ok((cmp::max(width, 1), cmp::max(height, height_for_rows(1))))
 }
@@ -0,0 +1,451 @@
+//! Just enough HTTP/1.1 for serving the art (one request per connection).
+
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::time::Instant;
+
+// Limit for the request line and headers (together).
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+/// A parsed request.
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads a request from the stream. The whole thing should arrive before the
+    /// deadline (to avoid clients hogging connections), and the body shouldn't be
+    /// larger than the given size.
+    ///
+    /// Errors are returned as responses, so that they can be sent to the client.
+    pub fn read<S: Read + Write>(
+        stream: &mut S,
+        max_body: usize,
+        deadline: Instant,
+    ) -> Result<Self, Response> {
+        let mut reader = BufReader::new(DeadlineReader { stream, deadline });
+        let mut head_size = 0;
+        let mut lines = vec![];
+        loop {
+            let mut line = vec![];
+            let n = reader
+                .by_ref()
+                .take((MAX_HEAD_SIZE - head_size) as u64 + 1)
+                .read_until(b'\n', &mut line)
+                .map_err(io_error)?;
+            head_size += n;
+            if n == 0 || head_size > MAX_HEAD_SIZE {
+                return Err(Response::error(400, "incomplete or oversized headers"));
+            }
+
+            let line = String::from_utf8(line)
+                .map_err(|_| Response::error(400, "headers should be UTF-8"))?;
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+
+            lines.push(line.to_owned());
+        }
+
+        let mut parts = lines.first().map(|l| l.split(' ')).into_iter().flatten();
+        let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(m), Some(t), Some(v)) if v.starts_with("HTTP/1.") => (m.to_owned(), t),
+            _ => return Err(Response::error(400, "malformed request line")),
+        };
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+                match (percent_decode(k), percent_decode(v)) {
+                    (Some(k), Some(v)) => Ok((k, v)),
+                    _ => Err(Response::error(400, "malformed query string")),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        let header = |name: &str| {
+            lines[1..].iter().find_map(|l| {
+                let (k, v) = l.split_once(':')?;
+                k.trim().eq_ignore_ascii_case(name).then_some(v.trim())
+            })
+        };
+
+        if header("transfer-encoding").is_some() {
+            return Err(Response::error(411, "request body needs a content length"));
+        }
+
+        let length = match header("content-length").map(str::parse::<usize>) {
+            Some(Ok(n)) if n > max_body => {
+                return Err(Response::error(
+                    413,
+                    format!("request body is larger than {} bytes", max_body),
+                ))
+            }
+            Some(Ok(n)) => n,
+            Some(Err(_)) => return Err(Response::error(400, "invalid content length")),
+            None => 0,
+        };
+
+        // Clients (like curl) wait for this before sending large bodies.
+        if length > 0 && header("expect").is_some_and(|v| v.eq_ignore_ascii_case("100-continue")) {
+            let stream = &mut reader.get_mut().stream;
+            stream
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .and_then(|_| stream.flush())
+                .map_err(io_error)?;
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(io_error)?;
+
+        Ok(Request {
+            method,
+            path: path.to_owned(),
+            query,
+            body,
+        })
+    }
+
+    /// Returns the (decoded) value of the given query parameter.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Writes the body of a streamed response.
+pub type BodyWriter = Box<dyn FnOnce(&mut dyn Write) -> io::Result<()> + Send>;
+
+/// Body of a response.
+pub enum Body {
+    Full(Vec<u8>),
+    /// Written as it's generated, with chunked transfer encoding. The final
+    /// (empty) chunk is only sent if the writer succeeds, so that clients can
+    /// tell when the body is incomplete.
+    Stream(BodyWriter),
+}
+
+/// A response, ready to be written to the client.
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Body,
+}
+
+impl Response {
+    pub fn new<B: Into<Vec<u8>>>(status: u16, content_type: &'static str, body: B) -> Self {
+        Response {
+            status,
+            content_type,
+            body: Body::Full(body.into()),
+        }
+    }
+
+    /// Response with a body that's written to the client as it's generated.
+    pub fn stream<F>(status: u16, content_type: &'static str, write: F) -> Self
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static,
+    {
+        Response {
+            status,
+            content_type,
+            body: Body::Stream(Box::new(write)),
+        }
+    }
+
+    /// Plain text response for errors.
+    pub fn error<M: Display>(status: u16, message: M) -> Self {
+        Self::new(
+            status,
+            "text/plain; charset=utf-8",
+            format!("{}\n", message),
+        )
+    }
+
+    /// Writes the response (and closes the connection, as far as the client's concerned).
+    pub fn write_to<W: Write>(self, mut writer: W) -> io::Result<()> {
+        let length = match &self.body {
+            Body::Full(body) => format!("Content-Length: {}\r\n", body.len()),
+            Body::Stream(_) => "Transfer-Encoding: chunked\r\n".into(),
+        };
+
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\n{}Connection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            length
+        );
+        writer.write_all(head.as_bytes())?;
+        match self.body {
+            Body::Full(body) => writer.write_all(&body)?,
+            Body::Stream(write) => {
+                write(&mut ChunkedWriter(&mut writer))?;
+                writer.write_all(b"0\r\n\r\n")?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+/// Writes each buffer as a chunk (for chunked transfer encoding).
+struct ChunkedWriter<W>(W);
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Empty chunks mark the end of the body.
+        if !buf.is_empty() {
+            write!(self.0, "{:x}\r\n", buf.len())?;
+            self.0.write_all(buf)?;
+            self.0.write_all(b"\r\n")?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Reader which fails once the deadline has passed. This is checked before each
+/// read, so that clients can't keep the connection by trickling bytes.
+struct DeadlineReader<'a, S> {
+    stream: &'a mut S,
+    deadline: Instant,
+}
+
+impl<S: Read> Read for DeadlineReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if Instant::now() > self.deadline {
+            return Err(io::Error::new(ErrorKind::TimedOut, "deadline has passed"));
+        }
+
+        self.stream.read(buf)
+    }
+}
+
+fn io_error(e: io::Error) -> Response {
+    match e.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => {
+            Response::error(408, "timed out reading the request")
+        }
+        _ => Response::error(400, format!("error reading the request: {}", e)),
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Decodes `%XX` escapes and `+` (for spaces) in query strings.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b'+' => bytes.push(b' '),
+            b => bytes.push(b),
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    /// Request from the client (with whatever the server writes back).
+    struct Client {
+        request: Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for Client {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.request.read(buf)
+        }
+    }
+
+    impl Write for Client {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn client(request: &[u8]) -> Client {
+        Client {
+            request: Cursor::new(request.to_vec()),
+            written: vec![],
+        }
+    }
+
+    fn read(request: &[u8], max_body: usize) -> Result<Request, Response> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        Request::read(&mut client(request), max_body, deadline)
+    }
+
+    fn error_status(request: &[u8], max_body: usize) -> u16 {
+        read(request, max_body)
+            .err()
+            .expect("expected an error")
+            .status
+    }
+
+    #[test]
+    fn parses_request() {
+        let req = read(
+            b"POST /render?width=80&ramp=%40%23+x&format= HTTP/1.1\r\n\
+              Host: localhost\r\nCONTENT-LENGTH: 5\r\n\r\nhello",
+            10,
+        )
+        .ok()
+        .expect("parsing request");
+
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/render");
+        assert_eq!(req.param("width"), Some("80"));
+        assert_eq!(req.param("ramp"), Some("@# x"));
+        assert_eq!(req.param("format"), Some(""));
+        assert_eq!(req.param("min"), None);
+        assert_eq!(req.body, b"hello");
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("a%20b+c").as_deref(), Some("a b c"));
+        assert_eq!(percent_decode("%E2%96%88%2b").as_deref(), Some("█+"));
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%4"), None);
+        assert_eq!(percent_decode("%ff"), None);
+
+        assert_eq!(error_status(b"GET /?ramp=%4 HTTP/1.1\r\n\r\n", 0), 400);
+        assert_eq!(error_status(b"GET /?%zz=1 HTTP/1.1\r\n\r\n", 0), 400);
+    }
+
+    #[test]
+    fn checks_content_length() {
+        let head = |length: &str| format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", length);
+        assert_eq!(error_status(head("11").as_bytes(), 10), 413);
+        assert_eq!(error_status(head("abc").as_bytes(), 10), 400);
+        assert_eq!(error_status(head("-1").as_bytes(), 10), 400);
+        // Body is shorter than the length.
+        assert_eq!(error_status((head("5") + "abc").as_bytes(), 10), 400);
+
+        // Without a length, there's no body.
+        let req = read(b"POST / HTTP/1.1\r\n\r\nignored", 10).ok().unwrap();
+        assert!(req.body.is_empty());
+        let chunked = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(error_status(chunked, 10), 411);
+    }
+
+    #[test]
+    fn rejects_malformed_heads() {
+        assert_eq!(error_status(b"", 0), 400);
+        assert_eq!(error_status(b"GET /\r\n\r\n", 0), 400);
+        assert_eq!(error_status(b"GET / SPDY/3\r\n\r\n", 0), 400);
+        assert_eq!(error_status(b"GET / HTTP/1.1\r\n", 0), 400);
+
+        let mut huge = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        huge.resize(MAX_HEAD_SIZE + 1, b'a');
+        huge.extend(b"\r\n\r\n");
+        assert_eq!(error_status(&huge, 0), 400);
+    }
+
+    #[test]
+    fn continues_large_uploads() {
+        let mut client =
+            client(b"POST / HTTP/1.1\r\nContent-Length: 2\r\nExpect: 100-continue\r\n\r\nhi");
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let req = Request::read(&mut client, 10, deadline).ok().unwrap();
+        assert_eq!(req.body, b"hi");
+        assert_eq!(client.written, b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+
+    #[test]
+    fn writes_responses() {
+        let mut out = vec![];
+        Response::new(200, "text/plain", "hi")
+            .write_to(&mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\
+             Connection: close\r\n\r\nhi"
+        );
+
+        let mut out = vec![];
+        Response::stream(200, "text/plain", |w| {
+            w.write_all(b"hello ")?;
+            w.write_all(b"")?;
+            w.write_all(b"world\n")
+        })
+        .write_to(&mut out)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\
+             Connection: close\r\n\r\n6\r\nhello \r\n6\r\nworld\n\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn incomplete_streams_are_left_open() {
+        let mut out = vec![];
+        let result = Response::stream(200, "text/plain", |w| {
+            w.write_all(b"partial")?;
+            Err(io::Error::new(ErrorKind::TimedOut, "too slow"))
+        })
+        .write_to(&mut out);
+
+        assert!(result.is_err());
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("7\r\npartial\r\n"), "{:?}", out);
+    }
+
+    #[test]
+    fn times_out_after_deadline() {
+        let deadline = Instant::now();
+        std::thread::sleep(Duration::from_millis(1));
+        let mut client = client(b"GET / HTTP/1.1\r\n\r\n");
+        let status = Request::read(&mut client, 0, deadline)
+            .err()
+            .unwrap()
+            .status;
+        assert_eq!(status, 408);
+    }
+}
@@ -0,0 +1,360 @@
+//! HTTP server for generating ASCII art on demand.
+
+mod http;
+
+use self::http::{Request, Response};
+use charcoal::export::{self, HtmlOptions, SvgOptions};
+use charcoal::{AsciiArtGenerator, Error, SizeLimits, SizePolicy};
+use image::{ImageError, Limits};
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{io, process, thread};
+
+const USAGE: &str = "\
+Usage: charcoal-server [OPTIONS]
+
+Options:
+    --addr <ADDR>           Address to listen on (default: 127.0.0.1:8000)
+    --max-body <BYTES>      Maximum size of uploaded images (default: 10485760)
+    --max-dimension <PX>    Maximum width and height of decoded images (default: 8192)
+    --max-width <WIDTH>     Maximum width of the art (default: 1000)
+    --max-rows <ROWS>       Maximum rows in the art (default: 1000)
+    --max-connections <N>   Maximum connections (and renders) in progress, beyond
+                            which requests are turned away (default: 32)
+    --timeout <SECS>        Timeout for reading requests and for rendering (default: 10)
+    -h, --help              Print this message
+
+Endpoints:
+    POST /render            Renders the image in the request body. Query parameters:
+                            min, max, gamma, width (like the web page), ramp
+                            (characters from densest to lightest) and format
+                            (text (default), html, svg or json with `json` feature).
+                            Text is streamed a band of rows at a time.";
+
+// Memory limit for decoders (on top of the dimension limits).
+const MAX_DECODER_ALLOC: u64 = 256 * 1024 * 1024;
+// Timeout for turning away connections when the server is busy.
+const BUSY_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+// Bands of streamed text which can be generated ahead of the client.
+const STREAM_BANDS_AHEAD: usize = 4;
+const TEXT_TYPE: &str = "text/plain; charset=utf-8";
+
+/// Settings for the server.
+struct Config {
+    addr: String,
+    max_body: usize,
+    max_dimension: u32,
+    max_width: u32,
+    max_rows: u32,
+    max_connections: usize,
+    timeout: Duration,
+}
+
+impl Config {
+    fn from_args() -> Result<Self, String> {
+        let mut config = Config {
+            addr: "127.0.0.1:8000".into(),
+            max_body: 10 * 1024 * 1024,
+            max_dimension: 8192,
+            max_width: 1000,
+            max_rows: 1000,
+            max_connections: 32,
+            timeout: Duration::from_secs(10),
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+
+            match arg.as_str() {
+                "--addr" => config.addr = value(&arg)?,
+                "--max-body" => config.max_body = parse(&arg, &value(&arg)?)?,
+                "--max-dimension" => config.max_dimension = parse(&arg, &value(&arg)?)?,
+                "--max-width" => config.max_width = parse(&arg, &value(&arg)?)?,
+                "--max-rows" => config.max_rows = parse(&arg, &value(&arg)?)?,
+                "--max-connections" => config.max_connections = parse(&arg, &value(&arg)?)?,
+                "--timeout" => config.timeout = Duration::from_secs(parse(&arg, &value(&arg)?)?),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for {}", value, name))
+}
+
+fn main() {
+    let config = Config::from_args().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let listener = TcpListener::bind(&config.addr).unwrap_or_else(|e| {
+        eprintln!("error: cannot listen on {}: {}", config.addr, e);
+        process::exit(1);
+    });
+
+    eprintln!("Listening on {}", config.addr);
+    let slots = Arc::new(Slots {
+        used: AtomicUsize::new(0),
+        max: config.max_connections,
+    });
+    let config = Arc::new(config);
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("error: cannot accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let Some(slot) = slots.take() else {
+            // This is small enough for the socket buffer, so it shouldn't block for long.
+            let _ = stream
+                .set_write_timeout(Some(BUSY_WRITE_TIMEOUT))
+                .and_then(|_| Response::error(503, "server is busy").write_to(&mut stream));
+            continue;
+        };
+
+        let config = config.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &config, slot) {
+                eprintln!("error: cannot write response: {}", e);
+            }
+        });
+    }
+}
+
+/// Counter for the connections in progress (along with their renders), so that
+/// the threads (and the work they're doing) are limited.
+struct Slots {
+    used: AtomicUsize,
+    max: usize,
+}
+
+impl Slots {
+    /// Takes a slot (if there's one free), which is released once the returned
+    /// guard (and all its clones) are dropped.
+    fn take(self: &Arc<Self>) -> Option<Arc<Slot>> {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < self.max).then_some(n + 1)
+            })
+            .ok()?;
+        Some(Arc::new(Slot(self.clone())))
+    }
+}
+
+struct Slot(Arc<Slots>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.used.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle(mut stream: TcpStream, config: &Arc<Config>, slot: Arc<Slot>) -> io::Result<()> {
+    stream.set_read_timeout(Some(config.timeout))?;
+    stream.set_write_timeout(Some(config.timeout))?;
+    let deadline = Instant::now() + config.timeout;
+    let response = match Request::read(&mut stream, config.max_body, deadline) {
+        Ok(req) => route(req, config, slot),
+        Err(resp) => resp,
+    };
+
+    response.write_to(&mut stream)
+}
+
+fn route(req: Request, config: &Arc<Config>, slot: Arc<Slot>) -> Response {
+    match (req.method.as_str(), req.path.as_str()) {
+        ("POST", "/render") => (),
+        (_, "/render") => return Response::error(405, "only POST is supported"),
+        _ => return Response::error(404, "not found"),
+    }
+
+    // Rendering happens in its own thread, so that we can give up on it if it's
+    // taking too long. It still runs to completion in the background though, so
+    // it holds on to the slot until then.
+    let (tx, rx) = mpsc::channel();
+    let c = config.clone();
+    let deadline = Instant::now() + config.timeout;
+    thread::spawn(move || {
+        let _slot = slot;
+        match render(&req, &c) {
+            Ok(Rendered::Text(gen)) => stream_text(&gen, &tx, deadline),
+            Ok(Rendered::Full(resp)) | Err(resp) => {
+                let _ = tx.send(resp);
+            }
+        }
+    });
+
+    rx.recv_timeout(config.timeout)
+        .unwrap_or_else(|_| Response::error(503, "timed out rendering the image"))
+}
+
+/// Art that's ready to be sent, or the generator for streaming the text.
+enum Rendered {
+    Full(Response),
+    Text(AsciiArtGenerator),
+}
+
+/// Sends a response which streams the text as it's generated (in this thread).
+/// The image is resized before responding, and the rest should be done by the
+/// deadline, or the response is left incomplete (so that clients can tell).
+fn stream_text(gen: &AsciiArtGenerator, tx: &mpsc::Sender<Response>, deadline: Instant) {
+    let mut stream = gen.processor().stream();
+    // Bands are followed by `None` once they're all done.
+    let (band_tx, band_rx) = mpsc::sync_channel::<Option<Vec<String>>>(STREAM_BANDS_AHEAD);
+    let body = move |w: &mut dyn Write| loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match band_rx.recv_timeout(timeout) {
+            Ok(Some(band)) => {
+                let mut text = band.join("\n");
+                text.push('\n');
+                w.write_all(text.as_bytes())?;
+            }
+            Ok(None) => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out rendering the image",
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("rendering stopped unexpectedly"))
+            }
+        }
+    };
+
+    if tx.send(Response::stream(200, TEXT_TYPE, body)).is_err() {
+        return; // we've already given up on this one
+    }
+
+    while let Some(band) = stream.next_band() {
+        if band_tx.send(Some(band)).is_err() {
+            return; // client is gone (or we ran out of time)
+        }
+    }
+
+    let _ = band_tx.send(None);
+}
+
+fn render(req: &Request, config: &Config) -> Result<Rendered, Response> {
+    let (min, max, gamma, width) = (
+        param::<u8>(req, "min")?,
+        param::<u8>(req, "max")?,
+        param::<f32>(req, "gamma")?,
+        param::<u32>(req, "width")?,
+    );
+
+    let format = req.param("format").unwrap_or("text");
+    let content_type = match format {
+        "text" => TEXT_TYPE,
+        "html" => "text/html; charset=utf-8",
+        "svg" => "image/svg+xml",
+        #[cfg(feature = "json")]
+        "json" => "application/json",
+        _ => {
+            return Err(Response::error(
+                400,
+                format!("unsupported format {:?}", format),
+            ))
+        }
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(config.max_dimension);
+    limits.max_image_height = Some(config.max_dimension);
+    limits.max_alloc = Some(MAX_DECODER_ALLOC);
+
+    let mut gen =
+        AsciiArtGenerator::from_bytes_with_limits(&req.body, limits).map_err(error_response)?;
+    // The default size (which wasn't asked for) is clamped to the limits, but
+    // widths which result in empty or huge art are rejected.
+    let limits = SizeLimits {
+        max_cols: Some(config.max_width),
+        max_rows: Some(config.max_rows),
+        policy: SizePolicy::Clamp,
+        ..SizeLimits::default()
+    };
+    gen.set_size_limits(limits).map_err(error_response)?;
+    gen.set_size_limits(SizeLimits {
+        policy: SizePolicy::Reject,
+        ..limits
+    })
+    .map_err(error_response)?;
+    if let Some(ramp) = req.param("ramp") {
+        gen.set_ramp(ramp).map_err(error_response)?;
+    }
+
+    if let Some(w) = width {
+        gen.set_width(w).map_err(error_response)?;
+    }
+
+    if let Some(m) = min {
+        gen.min_level.set(m);
+    }
+
+    if let Some(m) = max {
+        gen.max_level.set(m);
+    }
+
+    if let Some(m) = gamma {
+        gen.gamma.set(m);
+    }
+
+    // Text is streamed as it's generated (see `stream_text`).
+    if format == "text" {
+        return Ok(Rendered::Text(gen));
+    }
+
+    let art = gen.processor().generate();
+    let body = match format {
+        "html" => export::to_html(&art, &HtmlOptions::default()),
+        "svg" => export::to_svg(&art, &SvgOptions::default()),
+        #[cfg(feature = "json")]
+        "json" => export::to_json(&art),
+        _ => unreachable!("unsupported format {:?}", format),
+    };
+
+    Ok(Rendered::Full(Response::new(200, content_type, body)))
+}
+
+/// Parses the given query parameter (if it exists).
+fn param<T: std::str::FromStr>(req: &Request, name: &str) -> Result<Option<T>, Response> {
+    req.param(name)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| Response::error(400, format!("invalid value {:?} for {}", v, name)))
+        })
+        .transpose()
+}
+
+fn error_response(e: Error) -> Response {
+    let status = match &e {
+        Error::UnsupportedFormat(_) => 415,
+        Error::Image(ImageError::Limits(_)) => 413,
+        Error::Image(_) => 422,
+        _ => 400,
+    };
+
+    Response::error(status, e)
+}
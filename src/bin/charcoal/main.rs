@@ -0,0 +1,536 @@
+//! Command-line interface for generating ASCII art from images.
+
+mod player;
+
+use self::player::Player;
+use charcoal::export::{self, ChatOptions, HtmlOptions, PngOptions, SvgOptions};
+use charcoal::{AsciiArt, AsciiArtAnimation, Crop, FilterType, Transform};
+use image::Rgb;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "\
+Usage: charcoal [OPTIONS] <IMAGE>...
+
+Options:
+    --min <LEVEL>       Minimum level (0-255)
+    --max <LEVEL>       Maximum level (0-255)
+    --gamma <GAMMA>     Gamma correction (0.0-1.0)
+    --ramp <CHARS>      Characters to use, from the densest to the lightest
+    --crop <X,Y,W,H>    Crop the image to the given rectangle (in pixels) first
+    --rotate <DEGREES>  Rotate the image clockwise (after cropping)
+    --fill <RRGGBB>     Colour for the corners of rotated images (default: ffffff)
+    --flip <h|v>        Flip the image horizontally or vertically (after rotating)
+    --width <WIDTH>     Width of the image before generating the art
+    --cols <COLS>       Number of columns in the art
+    --rows <ROWS>       Number of rows in the art (the art fits within both,
+                        if both are given)
+    --fit-terminal      Fit the art within the current terminal
+    --filter <FILTER>   Filter for scaling down images: nearest, triangle,
+                        catmull-rom, gaussian or lanczos (default)
+    --upscale <FILTER>  Filter for scaling up small images (default: lanczos)
+    --exact-resize      Don't box-sample large images before resizing (slower)
+    --exact-blur        Use the exact Gaussian blur instead of box blurs (slower)
+    --play              Play the art in the terminal (in a loop). Use space
+                        to pause, arrow keys to step through frames and q to quit
+    -o, --output <PATH> Write the art to the given file instead of stdout (or
+                        to the given directory for multiple images)
+    -f, --format <FMT>  Output format: text (default), png, svg, html, markdown,
+                        irc, discord, json (with `json` feature) or msgpack
+                        (with `msgpack` feature)
+    --color             Use colours from the image (png, svg, html)
+    --fg <RRGGBB>       Foreground colour (png, svg, html, default: 000000)
+    --bg <RRGGBB>       Background colour (png, svg, html, default: ffffff)
+    --padding <PIXELS>  Padding around the art (png, default: 10)
+    --max-width <COLS>  Cut off lines beyond these many characters (markdown, irc, discord)
+    --max-lines <ROWS>  Cut off the art beyond these many lines (markdown, irc, discord)
+    -h, --help          Print this message
+
+Frames of animated images are separated by form feed (\\x0c) lines.
+Other formats are only supported for still images.
+
+The art for multiple images goes into files named after them (with the
+extension of the format) in the output directory. Images are processed in
+parallel with the `parallel` feature.";
+
+// Separates frames when writing animations.
+const FRAME_SEPARATOR: &str = "\x0c";
+
+/// Supported output formats.
+#[derive(Default, PartialEq)]
+enum Format {
+    #[default]
+    Text,
+    Png,
+    Svg,
+    Html,
+    Markdown,
+    Irc,
+    Discord,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+}
+
+impl Format {
+    /// Extension for files in this format.
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Png => "png",
+            Format::Svg => "svg",
+            Format::Html => "html",
+            Format::Markdown => "md",
+            Format::Irc => "irc",
+            Format::Discord => "ansi",
+            #[cfg(feature = "json")]
+            Format::Json => "json",
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack => "msgpack",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "text" | "txt" => Ok(Format::Text),
+            "png" => Ok(Format::Png),
+            "svg" => Ok(Format::Svg),
+            "html" => Ok(Format::Html),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "irc" => Ok(Format::Irc),
+            "discord" => Ok(Format::Discord),
+            #[cfg(feature = "json")]
+            "json" => Ok(Format::Json),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(Format::MsgPack),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Options collected from the command-line arguments.
+#[derive(Default)]
+struct Options {
+    min: Option<u8>,
+    max: Option<u8>,
+    gamma: Option<f32>,
+    ramp: Option<String>,
+    crop: Option<Crop>,
+    rotate: Option<f32>,
+    fill: Option<Rgb<u8>>,
+    flips: Vec<Transform>,
+    width: Option<u32>,
+    cols: Option<u32>,
+    rows: Option<u32>,
+    fit_terminal: bool,
+    filter: Option<FilterType>,
+    upscale_filter: Option<FilterType>,
+    exact_resize: bool,
+    exact_blur: bool,
+    play: bool,
+    output: Option<String>,
+    format: Format,
+    colored: bool,
+    foreground: Option<Rgb<u8>>,
+    background: Option<Rgb<u8>>,
+    padding: Option<u32>,
+    chat: ChatOptions,
+    inputs: Vec<String>,
+}
+
+impl Options {
+    fn from_args() -> Result<Self, String> {
+        let mut opts = Options::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+
+            match arg.as_str() {
+                "--min" => opts.min = Some(parse(&arg, &value(&arg)?)?),
+                "--max" => opts.max = Some(parse(&arg, &value(&arg)?)?),
+                "--gamma" => opts.gamma = Some(parse(&arg, &value(&arg)?)?),
+                "--ramp" => opts.ramp = Some(value(&arg)?),
+                "--crop" => opts.crop = Some(parse(&arg, &value(&arg)?)?),
+                "--rotate" => opts.rotate = Some(parse(&arg, &value(&arg)?)?),
+                "--fill" => opts.fill = Some(parse_color(&arg, &value(&arg)?)?),
+                "--flip" => opts.flips.push(parse_flip(&arg, &value(&arg)?)?),
+                "--width" => opts.width = Some(parse(&arg, &value(&arg)?)?),
+                "--cols" => opts.cols = Some(parse(&arg, &value(&arg)?)?),
+                "--rows" => opts.rows = Some(parse(&arg, &value(&arg)?)?),
+                "--fit-terminal" => opts.fit_terminal = true,
+                "--filter" => opts.filter = Some(parse_filter(&arg, &value(&arg)?)?),
+                "--exact-resize" => opts.exact_resize = true,
+                "--exact-blur" => opts.exact_blur = true,
+                "--upscale" => opts.upscale_filter = Some(parse_filter(&arg, &value(&arg)?)?),
+                "--play" => opts.play = true,
+                "-o" | "--output" => opts.output = Some(value(&arg)?),
+                "-f" | "--format" => opts.format = parse(&arg, &value(&arg)?)?,
+                "--color" => opts.colored = true,
+                "--fg" => opts.foreground = Some(parse_color(&arg, &value(&arg)?)?),
+                "--bg" => opts.background = Some(parse_color(&arg, &value(&arg)?)?),
+                "--padding" => opts.padding = Some(parse(&arg, &value(&arg)?)?),
+                "--max-width" => opts.chat.max_width = Some(parse(&arg, &value(&arg)?)?),
+                "--max-lines" => opts.chat.max_lines = Some(parse(&arg, &value(&arg)?)?),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ => opts.inputs.push(arg),
+            }
+        }
+
+        match opts.inputs.len() {
+            0 => return Err("missing input image".into()),
+            1 => (),
+            _ if opts.play => return Err("only one image can be played".into()),
+            _ if opts.output.is_none() => {
+                return Err("multiple images need an output directory".into())
+            }
+            _ => (),
+        }
+
+        Ok(opts)
+    }
+
+    fn png(&self) -> PngOptions {
+        let default = PngOptions::default();
+        PngOptions {
+            foreground: self.foreground.unwrap_or(default.foreground),
+            background: self.background.unwrap_or(default.background),
+            padding: self.padding.unwrap_or(default.padding),
+            colored: self.colored,
+        }
+    }
+
+    fn svg(&self) -> SvgOptions {
+        let default = SvgOptions::default();
+        SvgOptions {
+            foreground: self.foreground.unwrap_or(default.foreground),
+            background: self.background.or(default.background),
+            colored: self.colored,
+        }
+    }
+
+    fn html(&self, input: &str) -> HtmlOptions {
+        let default = HtmlOptions::default();
+        HtmlOptions {
+            foreground: self.foreground.unwrap_or(default.foreground),
+            background: self.background.unwrap_or(default.background),
+            colored: self.colored,
+            title: input.into(),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for {}", value, name))
+}
+
+fn parse_color(name: &str, value: &str) -> Result<Rgb<u8>, String> {
+    let hex = value.trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(c) if hex.len() == 6 => Ok(Rgb([(c >> 16) as u8, (c >> 8) as u8, c as u8])),
+        _ => Err(format!("invalid colour {:?} for {}", value, name)),
+    }
+}
+
+fn parse_flip(name: &str, value: &str) -> Result<Transform, String> {
+    match value.parse() {
+        Ok(t @ (Transform::FlipHorizontal | Transform::FlipVertical)) => Ok(t),
+        _ => Err(format!("invalid flip {:?} for {}", value, name)),
+    }
+}
+
+fn parse_filter(name: &str, value: &str) -> Result<FilterType, String> {
+    match value {
+        "nearest" => Ok(FilterType::Nearest),
+        "triangle" => Ok(FilterType::Triangle),
+        "catmull-rom" => Ok(FilterType::CatmullRom),
+        "gaussian" => Ok(FilterType::Gaussian),
+        "lanczos" => Ok(FilterType::Lanczos3),
+        _ => Err(format!("invalid filter {:?} for {}", value, name)),
+    }
+}
+
+fn main() {
+    let opts = Options::from_args().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    if let [input] = opts.inputs.as_slice() {
+        if let Err(e) = run(&opts, input, opts.output.as_deref().map(Path::new)) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+
+        return;
+    }
+
+    let dir = Path::new(opts.output.as_deref().expect("checked while parsing"));
+    let outputs = batch_outputs(&opts.inputs, dir, opts.format.extension()).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!("error: cannot create {}: {}", dir.display(), e);
+        process::exit(1);
+    }
+
+    if run_batch(&opts, &outputs) > 0 {
+        process::exit(1);
+    }
+}
+
+/// Returns the path in the output directory for each input (named after the
+/// input, with the extension of the format), making sure they're all different.
+fn batch_outputs(inputs: &[String], dir: &Path, ext: &str) -> Result<Vec<PathBuf>, String> {
+    let mut seen = HashMap::new();
+    inputs
+        .iter()
+        .map(|input| {
+            // Other dots are kept (`photo.v2.png` shouldn't become `photo.txt`).
+            let mut name = Path::new(input)
+                .file_stem()
+                .unwrap_or(input.as_ref())
+                .to_os_string();
+            name.push(".");
+            name.push(ext);
+
+            let output = dir.join(name);
+            match seen.insert(output.clone(), input) {
+                Some(other) => Err(format!(
+                    "{} and {} would both be written to {}",
+                    other,
+                    input,
+                    output.display()
+                )),
+                None => Ok(output),
+            }
+        })
+        .collect()
+}
+
+/// Generates the art for each image into the given output, and returns the
+/// number of images that failed.
+fn run_batch(opts: &Options, outputs: &[PathBuf]) -> usize {
+    let convert = |(input, output): (&String, &PathBuf)| {
+        run(opts, input, Some(output))
+            .map_err(|e| eprintln!("error: {}: {}", input, e))
+            .is_err()
+    };
+
+    #[cfg(feature = "parallel")]
+    let inputs = opts.inputs.par_iter().zip(outputs);
+    #[cfg(not(feature = "parallel"))]
+    let inputs = opts.inputs.iter().zip(outputs);
+
+    inputs.filter(|&i| convert(i)).count()
+}
+
+fn run(
+    opts: &Options,
+    input: &str,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = fs::read(input)?;
+    // Still images are just animations with a single frame.
+    let mut anim = AsciiArtAnimation::from_bytes(&bytes)?;
+    if opts.crop.is_some() {
+        anim.set_crop(opts.crop)?;
+    }
+
+    let fill = opts.fill.unwrap_or(Rgb([255, 255, 255]));
+    let mut transforms: Vec<_> = opts
+        .rotate
+        .and_then(|d| Transform::rotation(d, fill))
+        .into_iter()
+        .collect();
+    transforms.extend(&opts.flips);
+    if !transforms.is_empty() {
+        anim.set_transforms(transforms)?;
+    }
+
+    if let Some(w) = opts.width {
+        anim.set_width(w)?;
+    }
+
+    let (mut cols, mut rows) = (opts.cols, opts.rows);
+    if opts.fit_terminal {
+        let (term_cols, term_rows) = crossterm::terminal::size()?;
+        // Leave a line for the prompt (or the status line of the player).
+        let term_rows = term_rows.saturating_sub(1);
+        cols = Some(cols.map_or(term_cols as u32, |c| c.min(term_cols as u32)));
+        rows = Some(rows.map_or(term_rows as u32, |r| r.min(term_rows as u32)));
+    }
+
+    // Warn if the art doesn't have the size that was asked for (because of
+    // the limits), since that's easy to miss.
+    let warn = |asked: String, (c, r): (u32, u32)| {
+        eprintln!(
+            "warning: {}: art has {} columns and {} rows (asked for {})",
+            input, c, r, asked
+        )
+    };
+
+    match (cols, rows) {
+        (Some(c), Some(r)) => {
+            let grid = anim.fit(c, r)?;
+            // Fitting should fill up at least one side.
+            if (grid.0 < c && grid.1 < r) || grid.0 > c || grid.1 > r {
+                warn(format!("{} columns and {} rows", c, r), grid);
+            }
+        }
+        (Some(c), None) => {
+            let grid = anim.set_cols(c)?;
+            if grid.0 != c {
+                warn(format!("{} columns", c), grid);
+            }
+        }
+        (None, Some(r)) => {
+            let grid = anim.set_rows(r)?;
+            if grid.1 != r {
+                warn(format!("{} rows", r), grid);
+            }
+        }
+        (None, None) => (),
+    }
+
+    if let Some(m) = opts.min {
+        anim.min_level.set(m);
+    }
+
+    if let Some(m) = opts.max {
+        anim.max_level.set(m);
+    }
+
+    if let Some(m) = opts.gamma {
+        anim.gamma.set(m);
+    }
+
+    if let Some(r) = &opts.ramp {
+        anim.set_ramp(r)?;
+    }
+
+    if let Some(f) = opts.filter {
+        anim.filter.set(f);
+    }
+
+    if let Some(f) = opts.upscale_filter {
+        anim.upscale_filter.set(f);
+    }
+
+    anim.fast_resize.set(!opts.exact_resize);
+    anim.fast_blur.set(!opts.exact_blur);
+
+    if opts.play {
+        let frames: Vec<_> = anim.generate().collect();
+        return Ok(Player::new(&frames).play()?);
+    }
+
+    match output {
+        Some(path) => write_art(opts, input, BufWriter::new(File::create(path)?), &anim),
+        None => write_art(opts, input, io::stdout().lock(), &anim),
+    }
+}
+
+fn write_art<W: Write>(
+    opts: &Options,
+    input: &str,
+    mut writer: W,
+    anim: &AsciiArtAnimation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = match opts.format {
+        Format::Text => return write_frames(writer, anim),
+        Format::Png => export::to_png(&still(anim)?, &opts.png())?,
+        Format::Svg => export::to_svg(&still(anim)?, &opts.svg()).into_bytes(),
+        Format::Html => export::to_html(&still(anim)?, &opts.html(input)).into_bytes(),
+        Format::Markdown => export::to_markdown(&still(anim)?, &opts.chat).into_bytes(),
+        Format::Irc => export::to_irc(&still(anim)?, &opts.chat).into_bytes(),
+        Format::Discord => export::to_discord_ansi(&still(anim)?, &opts.chat).into_bytes(),
+        #[cfg(feature = "json")]
+        Format::Json => export::to_json(&still(anim)?).into_bytes(),
+        #[cfg(feature = "msgpack")]
+        Format::MsgPack => export::to_msgpack(&still(anim)?),
+    };
+
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Generates the art if we have a still image (i.e., a single frame).
+fn still(anim: &AsciiArtAnimation) -> Result<AsciiArt, Box<dyn std::error::Error>> {
+    match (anim.len(), anim.generate().next()) {
+        (1, Some(frame)) => Ok(frame.art),
+        _ => Err("animations can only be exported as text".into()),
+    }
+}
+
+/// Writes the rows of each frame as they're generated (so that huge art
+/// doesn't have to be kept around).
+fn write_frames<W: Write>(
+    mut writer: W,
+    anim: &AsciiArtAnimation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (i, (stream, _)) in anim.stream().enumerate() {
+        if i > 0 {
+            writeln!(writer, "{}", FRAME_SEPARATOR)?;
+        }
+
+        stream.write_to(&mut writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs(inputs: &[&str]) -> Result<Vec<PathBuf>, String> {
+        let inputs: Vec<_> = inputs.iter().map(|s| s.to_string()).collect();
+        batch_outputs(&inputs, Path::new("out"), "txt")
+    }
+
+    #[test]
+    fn batch_outputs_keep_the_stem() {
+        assert_eq!(
+            outputs(&["a/photo.png", "photo.v2.png", "b/notes"]).unwrap(),
+            [
+                Path::new("out/photo.txt"),
+                Path::new("out/photo.v2.txt"),
+                Path::new("out/notes.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn batch_outputs_are_unique() {
+        for inputs in [
+            &["a/x.png", "b/x.jpg"][..],
+            &["x.png", "x.png"],
+            &["x.png", "y.png", "x.gif"],
+        ] {
+            assert!(outputs(inputs).is_err(), "{:?}", inputs);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Terminal player for ASCII animations.
+
+use charcoal::AsciiArtFrame;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+// How long we wait for keys while paused, before checking again.
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const CLEAR_SCREEN: &str = "\x1b[2J";
+const CURSOR_HOME: &str = "\x1b[H";
+const CLEAR_LINE: &str = "\x1b[K";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+
+/// Raw mode (with the cursor hidden) for playing, which is turned off when
+/// this is dropped.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter<W: Write>(out: &mut W) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // From here on, the terminal is restored even if writing fails.
+        let raw = RawTerminal;
+        write!(out, "{}{}", HIDE_CURSOR, CLEAR_SCREEN)?;
+        Ok(raw)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // We're already on our way out, so there's nothing to do about errors.
+        let mut out = io::stdout();
+        let _ = write!(out, "{}\r\n", SHOW_CURSOR);
+        let _ = out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Things the viewer can ask for while playing.
+enum Action {
+    TogglePause,
+    Step(isize),
+    Quit,
+}
+
+/// Plays the frames (in a loop) until the viewer quits.
+///
+/// Keys: `space` pauses/resumes, `→`/`n` and `←`/`p` step forward and
+/// backward (pausing the animation) and `q`/`Esc`/`Ctrl+C` quits.
+pub struct Player<'a> {
+    frames: &'a [AsciiArtFrame],
+    current: usize,
+    paused: bool,
+}
+
+impl<'a> Player<'a> {
+    pub fn new(frames: &'a [AsciiArtFrame]) -> Self {
+        Player {
+            frames,
+            current: 0,
+            paused: false,
+        }
+    }
+
+    /// Takes over the terminal and plays the animation. The terminal is
+    /// restored once we're done (even if playing fails).
+    pub fn play(mut self) -> io::Result<()> {
+        if self.frames.is_empty() {
+            return Ok(());
+        }
+
+        let mut out = io::stdout().lock();
+        let _terminal = RawTerminal::enter(&mut out)?;
+        self.run(&mut out)
+    }
+
+    fn run<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        loop {
+            self.draw(out)?;
+            let deadline = Instant::now() + self.delay();
+            loop {
+                let timeout = if self.paused {
+                    PAUSED_POLL_INTERVAL
+                } else {
+                    deadline.saturating_duration_since(Instant::now())
+                };
+
+                match Self::next_action(timeout)? {
+                    Some(Action::Quit) => return Ok(()),
+                    Some(Action::TogglePause) => {
+                        self.paused = !self.paused;
+                        self.draw_status(out)?;
+                    }
+                    Some(Action::Step(n)) => {
+                        self.paused = true;
+                        self.advance(n);
+                        break;
+                    }
+                    None if !self.paused && Instant::now() >= deadline => {
+                        self.advance(1);
+                        break;
+                    }
+                    None => (),
+                }
+            }
+        }
+    }
+
+    /// Waits for a key (until the timeout) and maps it to an action.
+    fn next_action(timeout: Duration) -> io::Result<Option<Action>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => return Ok(None),
+        };
+
+        Ok(match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::Quit)
+            }
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char(' ') => Some(Action::TogglePause),
+            KeyCode::Right | KeyCode::Char('n') => Some(Action::Step(1)),
+            KeyCode::Left | KeyCode::Char('p') => Some(Action::Step(-1)),
+            _ => None,
+        })
+    }
+
+    #[inline]
+    fn delay(&self) -> Duration {
+        Duration::from_millis(self.frames[self.current].delay_ms as u64)
+    }
+
+    #[inline]
+    fn advance(&mut self, n: isize) {
+        let len = self.frames.len() as isize;
+        self.current = (self.current as isize + n).rem_euclid(len) as usize;
+    }
+
+    fn draw<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "{}", CURSOR_HOME)?;
+        // We're in raw mode, so we need carriage returns as well.
+        for row in &self.frames[self.current].art.rows {
+            write!(out, "{}\r\n", row)?;
+        }
+
+        self.draw_status(out)
+    }
+
+    fn draw_status<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let rows = self.frames[self.current].art.rows.len();
+        write!(
+            out,
+            "\x1b[{};1H{}frame {}/{}{}  [space] pause  [←/→] step  [q] quit",
+            rows + 1,
+            CLEAR_LINE,
+            self.current + 1,
+            self.frames.len(),
+            if self.paused { " (paused)" } else { "" },
+        )?;
+        out.flush()
+    }
+}
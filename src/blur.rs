@@ -0,0 +1,138 @@
+//! Fast approximation of the Gaussian blur.
+
+use image::DynamicImage;
+
+// Number of box blurs (three is close enough to a Gaussian).
+const PASSES: usize = 3;
+
+/// Blurs the image with successive box blurs, which approximate a Gaussian blur
+/// with the given standard deviation. Each pass is separable and takes constant
+/// time per pixel, regardless of the radius.
+///
+/// Grayscale images are blurred as luma, and everything else as RGB (alpha isn't
+/// used by the later stages anyway).
+pub(crate) fn box_blur(img: &DynamicImage, sigma: f32) -> DynamicImage {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    if img.color().has_color() {
+        let mut buf = img.to_rgb8();
+        blur_channels::<3>(&mut buf, width, height, sigma);
+        DynamicImage::ImageRgb8(buf)
+    } else {
+        let mut buf = img.to_luma8();
+        blur_channels::<1>(&mut buf, width, height, sigma);
+        DynamicImage::ImageLuma8(buf)
+    }
+}
+
+/// Blurs the raw buffer (with `C` interleaved channels) in place.
+fn blur_channels<const C: usize>(buf: &mut [u8], width: usize, height: usize, sigma: f32) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // Extending the edges in each pass isn't the same as blurring the image with
+    // its edges extended (which is what the Gaussian blur does, and matters for
+    // small images), so the edges are extended by the reach of all the passes first.
+    let radii = box_radii(sigma);
+    let pad = radii.iter().sum::<usize>();
+    let (padded_width, padded_height) = (width + 2 * pad, height + 2 * pad);
+    let stride = width * C;
+
+    // Passes work on the sums of the boxes (without dividing them by the box size),
+    // so that they're exact (and don't depend on where the image starts, which
+    // matters for streaming), and the values are divided (and rounded) only once.
+    let mut values = Vec::with_capacity(padded_width * padded_height * C);
+    for y in 0..padded_height {
+        let row = &buf[y.saturating_sub(pad).min(height - 1) * stride..][..stride];
+        let (first, last) = (&row[..C], &row[stride - C..]);
+        values.extend(first.iter().cycle().take(pad * C).map(|&v| v as u64));
+        values.extend(row.iter().map(|&v| v as u64));
+        values.extend(last.iter().cycle().take(pad * C).map(|&v| v as u64));
+    }
+
+    let mut scratch = vec![0; values.len()];
+    for radius in radii {
+        blur_rows::<C>(&values, &mut scratch, padded_width, radius);
+        blur_cols(&scratch, &mut values, padded_width * C, radius);
+    }
+
+    let norm: u64 = radii.iter().map(|&r| (2 * r as u64 + 1).pow(2)).product();
+    for (y, row) in buf.chunks_exact_mut(stride).enumerate() {
+        let src = &values[((y + pad) * padded_width + pad) * C..][..stride];
+        for (b, v) in row.iter_mut().zip(src) {
+            *b = ((v + norm / 2) / norm) as u8;
+        }
+    }
+}
+
+/// Radii of the boxes for approximating a Gaussian blur with the given standard
+/// deviation (see "Fast Almost-Gaussian Filtering" by Peter Kovesi).
+fn box_radii(sigma: f32) -> [usize; PASSES] {
+    let n = PASSES as f32;
+    let ideal = (12.0 * sigma * sigma / n + 1.0).sqrt();
+    // Boxes have odd widths (lower, or lower + 2), so that they're centered.
+    let lower = match ideal.floor() as usize {
+        w if w % 2 == 0 => w.saturating_sub(1).max(1),
+        w => w,
+    };
+
+    let l = lower as f32;
+    let m = ((12.0 * sigma * sigma - n * l * l - 4.0 * n * l - 3.0 * n) / (-4.0 * l - 4.0)).round();
+    std::array::from_fn(|i| {
+        if (i as f32) < m {
+            (lower - 1) / 2
+        } else {
+            lower.div_ceil(2)
+        }
+    })
+}
+
+/// Box blur along each row (with the edges extended), without dividing by the box size.
+fn blur_rows<const C: usize>(src: &[u64], dst: &mut [u64], width: usize, radius: usize) {
+    let last = width - 1;
+    for (src, dst) in src
+        .chunks_exact(width * C)
+        .zip(dst.chunks_exact_mut(width * C))
+    {
+        let (src, dst) = (src.as_chunks::<C>().0, dst.as_chunks_mut::<C>().0);
+        let at = |x: usize| &src[x.min(last)];
+        // Window for the first pixel (with the left edge repeated).
+        let mut sums = at(0).map(|v| (radius + 1) as u64 * v);
+        for x in 1..=radius {
+            for (s, v) in sums.iter_mut().zip(at(x)) {
+                *s += v;
+            }
+        }
+
+        for (x, d) in dst.iter_mut().enumerate() {
+            let (add, sub) = (at(x + radius + 1), at(x.saturating_sub(radius)));
+            for c in 0..C {
+                d[c] = sums[c];
+                sums[c] = sums[c] + add[c] - sub[c];
+            }
+        }
+    }
+}
+
+/// Box blur along each column (with the edges extended), a row at a time.
+fn blur_cols(src: &[u64], dst: &mut [u64], stride: usize, radius: usize) {
+    let rows: Vec<_> = src.chunks_exact(stride).collect();
+    let last = rows.len() - 1;
+    let row = |y: usize| rows[y.min(last)];
+
+    // Sums for the first row (with the top edge repeated).
+    let mut sums: Vec<_> = row(0).iter().map(|&v| (radius + 1) as u64 * v).collect();
+    for y in 1..=radius {
+        for (s, v) in sums.iter_mut().zip(row(y)) {
+            *s += v;
+        }
+    }
+
+    for (y, dst) in dst.chunks_exact_mut(stride).enumerate() {
+        dst.copy_from_slice(&sums);
+        let (add, sub) = (row(y + radius + 1), row(y.saturating_sub(radius)));
+        for ((s, a), r) in sums.iter_mut().zip(add).zip(sub) {
+            *s = *s + a - r;
+        }
+    }
+}
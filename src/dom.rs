@@ -1,7 +1,11 @@
-use crate::art::AsciiArtGenerator;
+use crate::anim::{AsciiArtAnimation, Decoded};
+use crate::art::{AsciiArt, AsciiArtGenerator, Crop, STREAM_BAND_ROWS};
+use crate::error::Error;
+use crate::limits::{SizeLimits, SizePolicy};
+use crate::transform::Transform;
 
 use base64::prelude::*;
-use image::DynamicImage;
+use image::{DynamicImage, Limits, RgbaImage};
 use js_sys::Uint8Array;
 use wasm_bindgen::prelude::*;
 
@@ -11,12 +15,101 @@ use std::io::{BufWriter, Cursor};
 use std::rc::Rc;
 
 const THUMB_HEIGHT: u32 = 50;
+// GIF delays are in centiseconds, so this is enough for playing them.
+const ANIMATION_TICK_MS: u32 = 10;
+// Width of the webcam frames (captured to canvas) before generating the art.
+const WEBCAM_WIDTH: u32 = 160;
+// `HAVE_CURRENT_DATA` ready state of media elements.
+const MEDIA_HAVE_CURRENT_DATA: u16 = 2;
+// Selections smaller than this (in thumbnail pixels) are treated as clicks.
+const MIN_CROP_DRAG: i32 = 3;
+// Limits for the art on the page (widths from the URL are clamped to these).
+const MAX_COLS: u32 = 1000;
+const MAX_ROWS: u32 = 1000;
+const PAGE_LIMITS: SizeLimits = SizeLimits {
+    max_source_width: None,
+    max_source_height: None,
+    max_source_pixels: None,
+    max_cols: Some(MAX_COLS),
+    max_rows: Some(MAX_ROWS),
+    policy: SizePolicy::Clamp,
+};
+// Limits for decoding the images on the page (all frames of an animation are kept).
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+const MAX_FRAMES: usize = 1000;
+
+thread_local! {
+    // Whether the time taken by each stage should be logged to the console.
+    static LOG_TIMINGS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Adjustments made through the page for the image that's currently loaded.
+#[derive(Default)]
+struct Adjustments {
+    crop: Option<Crop>,
+    transforms: Vec<Transform>,
+    /// Region of the source image that's shown in the thumbnails (i.e., the crop
+    /// rectangle, or the whole image).
+    region: Option<Crop>,
+}
+
+impl Adjustments {
+    fn apply(&mut self, gen: &mut AsciiArtGenerator) {
+        if let Err(e) = gen.set_crop(self.crop) {
+            console_log!("Ignoring crop: {}", e);
+            self.crop = None;
+        }
+
+        if let Err(e) = gen.set_transforms(self.transforms.clone()) {
+            console_log!("Ignoring transforms: {}", e);
+            self.transforms.clear();
+        }
+
+        let params = gen.params();
+        self.region = Some(gen.crop().unwrap_or(Crop {
+            x: 0,
+            y: 0,
+            width: params.source_width,
+            height: params.source_height,
+        }));
+    }
+
+    /// Maps the selection (between the given points) in a thumbnail of the given
+    /// size to a crop rectangle in the source image.
+    fn selection_to_crop(
+        &self,
+        (x0, y0): (i32, i32),
+        (x1, y1): (i32, i32),
+        (w, h): (i32, i32),
+    ) -> Option<Crop> {
+        let r = self.region.filter(|_| w > 0 && h > 0)?;
+        let norm = |v: i32, size: i32| v.clamp(0, size) as f32 / size as f32;
+        // Undo the transforms to find the corners in the (cropped) source image.
+        let unmap = |p| self.transforms.iter().rev().try_fold(p, |p, t| t.unmap(p));
+        let (ax, ay) = unmap((norm(x0, w), norm(y0, h)))?;
+        let (bx, by) = unmap((norm(x1, w), norm(y1, h)))?;
+
+        let scale = |v: f32, actual: u32| (v * actual as f32) as u32;
+        let (left, right) = (scale(ax.min(bx), r.width), scale(ax.max(bx), r.width));
+        let (top, bottom) = (scale(ay.min(by), r.height), scale(ay.max(by), r.height));
+        Some(Crop {
+            x: r.x + left,
+            y: r.y + top,
+            width: cmp::max(right - left, 1),
+            height: cmp::max(bottom - top, 1),
+        })
+    }
+}
 
 /// A thing for reading files and injecting the art.
 pub struct DomAsciiArtInjector {
     pub window: Rc<web_sys::Window>,
     pub document: Rc<web_sys::Document>,
     pub keeper: Rc<RefCell<TimingEventKeeper>>,
+    /// Keeper for the animation that's currently playing (if any).
+    pub player: Rc<RefCell<TimingEventKeeper>>,
+    /// The art that was drawn last (used for downloads).
+    pub art: Rc<RefCell<Option<AsciiArt>>>,
 }
 
 impl DomAsciiArtInjector {
@@ -30,18 +123,27 @@ impl DomAsciiArtInjector {
             window,
             document,
             keeper: TimingEventKeeper::new(),
+            player: TimingEventKeeper::new(),
+            art: Rc::new(RefCell::new(None)),
         }
     }
 
+    /// Logs the time taken for decoding the image and each stage of the pipeline
+    /// to the console (for all injectors), for measuring performance in browsers.
+    pub fn set_log_timings(&self, enabled: bool) {
+        LOG_TIMINGS.with(|l| l.set(enabled));
+    }
+
     /// Inject into the `<pre>` element matching the given ID using the given image data.
     pub fn inject_from_data(&self, pre_elem_id: &str, buffer: &[u8]) -> Result<(), JsValue> {
         let pre = get_elem_by_id!(self.document > pre_elem_id => web_sys::HtmlPreElement)?;
         let gen = AsciiArtGenerator::from_bytes(buffer)
             .map(Rc::new)
             .expect("failed to load demo.");
+        gen.fast_blur.set(true);
         Self::inject_from_data_using_document(
             gen,
-            &self.document,
+            &Rc::new(RefCell::new(None)), // we don't offer downloads for this
             &self.keeper,
             &pre,
             0,
@@ -64,6 +166,8 @@ impl DomAsciiArtInjector {
         max: Option<u8>,
         gamma: Option<f32>,
         width: Option<u32>,
+        crop: Option<Crop>,
+        transforms: Vec<Transform>,
         timeout_ms: u32,
         final_callback: F,
     ) -> Result<(), JsValue>
@@ -76,7 +180,8 @@ impl DomAsciiArtInjector {
         xhr.open("GET", url)?;
         xhr.set_response_type(web_sys::XmlHttpRequestResponseType::Arraybuffer);
 
-        let (x, d, k) = (xhr.clone(), self.document.clone(), self.keeper.clone());
+        let (x, a, k) = (xhr.clone(), self.art.clone(), self.keeper.clone());
+        let player = self.player.clone();
         let download = Closure::wrap(Box::new(move |_: web_sys::Event| {
             if x.ready_state() != web_sys::XmlHttpRequest::DONE {
                 console_log!("Ajax not ready yet.");
@@ -93,16 +198,72 @@ impl DomAsciiArtInjector {
             let buffer = Uint8Array::new(&value);
             let mut bytes = vec![0; buffer.length() as usize];
             buffer.copy_to(&mut bytes);
-            let gen = AsciiArtGenerator::from_bytes(&bytes)
-                .map(|mut gen| {
-                    if let Some(w) = width {
-                        gen.set_width(w);
+            player.borrow_mut().clear(); // stop the previous animation (if any)
+            let mut gen = match time_stage("decode", || decode(&bytes)) {
+                Ok(Decoded::Still(gen)) => gen,
+                Ok(Decoded::Animated(mut anim)) => {
+                    a.borrow_mut().take(); // we don't offer downloads for animations
+                    if let Err(e) = anim.set_size_limits(PAGE_LIMITS) {
+                        console_log!("Ignoring size limits: {}", e);
+                    }
+
+                    if let Err(e) = anim.set_crop(crop) {
+                        console_log!("Ignoring crop: {}", e);
                     }
 
-                    gen
-                })
-                .map(Rc::new)
-                .expect("failed to load image.");
+                    if let Err(e) = anim.set_transforms(transforms.clone()) {
+                        console_log!("Ignoring transforms: {}", e);
+                    }
+
+                    if let Some(Err(e)) = width.map(|w| anim.set_width(w)) {
+                        console_log!("Ignoring width: {}", e);
+                    }
+
+                    if let Some(m) = min {
+                        anim.min_level.set(m);
+                    }
+
+                    if let Some(m) = max {
+                        anim.max_level.set(m);
+                    }
+
+                    if let Some(m) = gamma {
+                        anim.gamma.set(m);
+                    }
+
+                    console_log!("Loaded {} frames", anim.len());
+                    return Self::play_animation(
+                        anim,
+                        &k,
+                        &player,
+                        &pre,
+                        timeout_ms,
+                        final_callback.clone(),
+                    );
+                }
+                Err(e) => {
+                    console_log!("Failed to load image: {}", e);
+                    return;
+                }
+            };
+
+            if let Err(e) = gen.set_size_limits(PAGE_LIMITS) {
+                console_log!("Ignoring size limits: {}", e);
+            }
+
+            if let Err(e) = gen.set_crop(crop) {
+                console_log!("Ignoring crop: {}", e);
+            }
+
+            if let Err(e) = gen.set_transforms(transforms.clone()) {
+                console_log!("Ignoring transforms: {}", e);
+            }
+
+            if let Some(Err(e)) = width.map(|w| gen.set_width(w)) {
+                console_log!("Ignoring width: {}", e);
+            }
+
+            let gen = Rc::new(gen);
 
             if let Some(m) = min {
                 gen.min_level.set(m);
@@ -119,7 +280,7 @@ impl DomAsciiArtInjector {
             console_log!("Loaded {} bytes", bytes.len());
             Self::inject_from_data_using_document(
                 gen,
-                &d,
+                &a,
                 &k,
                 &pre,
                 timeout_ms,
@@ -135,6 +296,136 @@ impl DomAsciiArtInjector {
         Ok(())
     }
 
+    /// Captures frames from the webcam (through `getUserMedia`) and redraws the `<pre>`
+    /// element with the art at the given frame rate. The frames are drawn onto a canvas
+    /// and its pixels are fed directly to the generator (no decoding involved).
+    pub fn inject_from_webcam(
+        &self,
+        pre_elem_id: &str,
+        min: Option<u8>,
+        max: Option<u8>,
+        gamma: Option<f32>,
+        width: Option<u32>,
+        fps: u32,
+    ) -> Result<(), JsValue> {
+        let pre = get_elem_by_id!(self.document > pre_elem_id => web_sys::HtmlPreElement)?;
+        let video = self
+            .document
+            .create_element("video")?
+            .dyn_into::<web_sys::HtmlVideoElement>()
+            .map(Rc::new)?;
+        video.set_muted(true);
+        let canvas = self
+            .document
+            .create_element("canvas")?
+            .dyn_into::<web_sys::HtmlCanvasElement>()?;
+        let ctx = canvas
+            .get_context("2d")?
+            .expect("getting canvas context")
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()?;
+
+        let constraints = web_sys::MediaStreamConstraints::new();
+        constraints.set_video_bool(true);
+        let promise = self
+            .window
+            .navigator()
+            .media_devices()?
+            .get_user_media_with_constraints(&constraints)?;
+
+        let (player, art) = (self.player.clone(), self.art.clone());
+        let interval_ms = 1000 / cmp::max(fps, 1);
+        let width = width.unwrap_or(WEBCAM_WIDTH);
+        let start = Closure::once(Box::new(move |stream: JsValue| {
+            let stream = match stream.dyn_into::<web_sys::MediaStream>() {
+                Ok(s) => s,
+                Err(e) => {
+                    console_log!("Unexpected webcam stream: {:?}", e);
+                    return;
+                }
+            };
+
+            video.set_src_object(Some(&stream));
+            if let Err(e) = video.play() {
+                console_log!("Cannot play webcam stream: {:?}", e);
+                return;
+            }
+
+            console_log!("Webcam stream started.");
+            player.borrow_mut().clear(); // stop the previous animation (if any)
+
+            // Move the player inside to prevent clearing the interval (this closure
+            // is the only one holding it once the promise is done).
+            let keeper = player.clone();
+            player.borrow_mut().add_repetitive(
+                move || {
+                    let _keeper = &keeper;
+                    if video.ready_state() < MEDIA_HAVE_CURRENT_DATA
+                        || video.video_width() == 0
+                        || video.video_height() == 0
+                    {
+                        return;
+                    }
+
+                    let w = cmp::min(width, video.video_width());
+                    let h = cmp::max(video.video_height() * w / video.video_width(), 1);
+                    canvas.set_width(w);
+                    canvas.set_height(h);
+                    if let Err(e) = ctx.draw_image_with_html_video_element_and_dw_and_dh(
+                        &video, 0.0, 0.0, w as f64, h as f64,
+                    ) {
+                        console_log!("Cannot draw video frame: {:?}", e);
+                        return;
+                    }
+
+                    let data = match ctx.get_image_data(0.0, 0.0, w as f64, h as f64) {
+                        Ok(d) => d.data(),
+                        Err(e) => {
+                            console_log!("Cannot get frame pixels: {:?}", e);
+                            return;
+                        }
+                    };
+
+                    let img = RgbaImage::from_raw(w, h, data.0).expect("invalid frame buffer");
+                    let gen = match AsciiArtGenerator::from_image(DynamicImage::ImageRgba8(img)) {
+                        Ok(gen) => gen,
+                        Err(e) => {
+                            console_log!("Skipping frame: {}", e);
+                            return;
+                        }
+                    };
+
+                    gen.fast_blur.set(true);
+
+                    if let Some(m) = min {
+                        gen.min_level.set(m);
+                    }
+
+                    if let Some(m) = max {
+                        gen.max_level.set(m);
+                    }
+
+                    if let Some(m) = gamma {
+                        gen.gamma.set(m);
+                    }
+
+                    let frame = gen.processor().generate();
+                    pre.set_text_content(Some(&frame.rows.join("\n")));
+                    *art.borrow_mut() = Some(frame);
+                },
+                interval_ms,
+            );
+        }) as Box<dyn FnOnce(JsValue)>);
+
+        let fail = Closure::once(Box::new(|e: JsValue| {
+            console_log!("Cannot access webcam: {:?}", e);
+        }) as Box<dyn FnOnce(JsValue)>);
+
+        let _ = promise.then(&start).catch(&fail);
+        start.forget();
+        fail.forget();
+        Ok(())
+    }
+
     /// Adds an event listener to watch and update the `<pre>` element
     /// whenever a file is loaded.
     pub fn inject_on_file_loads<F>(
@@ -155,6 +446,9 @@ impl DomAsciiArtInjector {
         let input = get_elem_by_id!(self.document > input_elem_id => web_sys::HtmlInputElement)?;
         input.set_value(""); // reset input element
 
+        let adjust = Rc::new(RefCell::new(Adjustments::default()));
+        self.add_adjustment_listeners(prog.clone(), input.clone(), adjust.clone())?;
+
         let min_inp =
             query_selector!(self.document > "#min-level > .range" => web_sys::HtmlInputElement)?;
         let max_inp =
@@ -164,6 +458,7 @@ impl DomAsciiArtInjector {
 
         {
             let (r, k, doc) = (reader.clone(), self.keeper.clone(), self.document.clone());
+            let (player, art) = (self.player.clone(), self.art.clone());
             let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
                 // Something has changed. Reset progress and get new values and buffer.
                 prog.set_inner_html("");
@@ -177,9 +472,38 @@ impl DomAsciiArtInjector {
                 let buffer = Uint8Array::new(&value);
                 let mut bytes = vec![0; buffer.length() as usize];
                 buffer.copy_to(&mut bytes);
-                let gen = AsciiArtGenerator::from_bytes(&bytes)
-                    .map(Rc::new)
-                    .expect("failed to load image.");
+                player.borrow_mut().clear(); // stop the previous animation (if any)
+                let mut gen = match time_stage("decode", || decode(&bytes)) {
+                    Ok(Decoded::Still(gen)) => gen,
+                    Ok(Decoded::Animated(mut anim)) => {
+                        art.borrow_mut().take(); // we don't offer downloads for animations
+                        if let Err(e) = anim.set_transforms(adjust.borrow().transforms.clone()) {
+                            console_log!("Ignoring transforms: {}", e);
+                        }
+
+                        anim.min_level.set(min);
+                        anim.max_level.set(max);
+                        anim.gamma.set(gamma);
+
+                        console_log!("Loaded {} frames", anim.len());
+                        return Self::play_animation(
+                            anim,
+                            &k,
+                            &player,
+                            &pre,
+                            timeout_ms,
+                            final_callback.clone(),
+                        );
+                    }
+                    Err(e) => {
+                        console_log!("Failed to load image: {}", e);
+                        return;
+                    }
+                };
+
+                adjust.borrow_mut().apply(&mut gen);
+
+                let gen = Rc::new(gen);
                 gen.min_level.set(min);
                 gen.max_level.set(max);
                 gen.gamma.set(gamma);
@@ -188,7 +512,7 @@ impl DomAsciiArtInjector {
                 let (doc, prog) = (doc.clone(), prog.clone());
                 Self::inject_from_data_using_document(
                     gen,
-                    &doc.clone(),
+                    &art,
                     &k,
                     &pre,
                     timeout_ms,
@@ -229,6 +553,182 @@ impl DomAsciiArtInjector {
         self.add_file_listener(input, reader)
     }
 
+    /// Adds a click listener to the button for downloading the art that was drawn
+    /// last, using the given function to export it.
+    pub fn add_download_button<F>(
+        &self,
+        button_id: &str,
+        file_name: &str,
+        mime: &str,
+        export: F,
+    ) -> Result<(), JsValue>
+    where
+        F: Fn(&AsciiArt) -> Result<Vec<u8>, Error> + 'static,
+    {
+        let button = get_elem_by_id!(self.document > button_id => web_sys::EventTarget)?;
+        let (doc, art) = (self.document.clone(), self.art.clone());
+        let (file_name, mime) = (file_name.to_owned(), mime.to_owned());
+        let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            let bytes = match art.borrow().as_ref().map(&export) {
+                Some(Ok(bytes)) => bytes,
+                Some(Err(e)) => {
+                    console_log!("Failed to export art: {}", e);
+                    return;
+                }
+                None => {
+                    console_log!("Nothing to download.");
+                    return;
+                }
+            };
+
+            let link = doc
+                .create_element("a")
+                .expect("creating link")
+                .dyn_into::<web_sys::HtmlElement>()
+                .expect("casting link");
+            let url = format!("data:{};base64,{}", mime, BASE64_STANDARD.encode(bytes));
+            link.set_attribute("href", &url).expect("setting link");
+            link.set_attribute("download", &file_name)
+                .expect("setting file name");
+            link.click();
+        }) as Box<dyn FnMut(_)>);
+
+        button.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+        Ok(())
+    }
+
+    /// Adds listeners for the adjustments made through the page - selecting a crop
+    /// rectangle by dragging over the thumbnails (double click removes the crop) and
+    /// the rotate and flip buttons. The art is regenerated by emitting a change event
+    /// at the input, and the adjustments are forgotten whenever a new file is picked.
+    fn add_adjustment_listeners(
+        &self,
+        prog: Rc<web_sys::Element>,
+        input: Rc<web_sys::HtmlInputElement>,
+        adjust: Rc<RefCell<Adjustments>>,
+    ) -> Result<(), JsValue> {
+        // Thumbnail being dragged over, the selection overlay and the starting point.
+        type Drag = (web_sys::HtmlImageElement, web_sys::HtmlElement, i32, i32);
+        let drag: Rc<RefCell<Option<Drag>>> = Rc::new(RefCell::new(None));
+
+        let (d, doc, p) = (drag.clone(), self.document.clone(), prog.clone());
+        let start = Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
+            let img = match e.target().and_then(|t| t.dyn_into().ok()) {
+                Some(img) => img,
+                None => return,
+            };
+
+            e.prevent_default(); // don't drag the image around
+            let overlay = doc
+                .create_element("div")
+                .expect("creating overlay")
+                .dyn_into::<web_sys::HtmlElement>()
+                .expect("casting overlay");
+            overlay.set_class_name("crop-selection");
+            p.append_child(&overlay).expect("adding overlay");
+            *d.borrow_mut() = Some((img, overlay, e.offset_x(), e.offset_y()));
+        }) as Box<dyn FnMut(_)>);
+
+        let d = drag.clone();
+        let update = Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
+            if let Some((img, overlay, x0, y0)) = &*d.borrow() {
+                if e.target().as_ref() != Some(img.as_ref()) {
+                    return;
+                }
+
+                let (x1, y1) = (e.offset_x(), e.offset_y());
+                let style = format!(
+                    "left: {}px; top: {}px; width: {}px; height: {}px;",
+                    img.offset_left() + cmp::min(*x0, x1),
+                    img.offset_top() + cmp::min(*y0, y1),
+                    (x1 - x0).abs(),
+                    (y1 - y0).abs(),
+                );
+                overlay
+                    .set_attribute("style", &style)
+                    .expect("moving overlay");
+            }
+        }) as Box<dyn FnMut(_)>);
+
+        let (a, inp) = (adjust.clone(), input.clone());
+        let finish = Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
+            let (img, overlay, x0, y0) = match drag.borrow_mut().take() {
+                Some(drag) => drag,
+                None => return,
+            };
+
+            overlay.remove();
+            let (x1, y1) = match e.target() {
+                Some(t) if &t == img.as_ref() => (e.offset_x(), e.offset_y()),
+                _ => return,
+            };
+
+            if (x1 - x0).abs() < MIN_CROP_DRAG || (y1 - y0).abs() < MIN_CROP_DRAG {
+                return;
+            }
+
+            let size = (img.client_width(), img.client_height());
+            let crop = match a.borrow().selection_to_crop((x0, y0), (x1, y1), size) {
+                Some(crop) => crop,
+                None => {
+                    console_log!("Cannot crop this image.");
+                    return;
+                }
+            };
+
+            console_log!("Cropping to {:?}", crop);
+            a.borrow_mut().crop = Some(crop);
+            emit_change(&inp);
+        }) as Box<dyn FnMut(_)>);
+
+        let (a, inp) = (adjust.clone(), input.clone());
+        let uncrop = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            let cropped = a.borrow_mut().crop.take().is_some();
+            if cropped {
+                emit_change(&inp);
+            }
+        }) as Box<dyn FnMut(_)>);
+
+        for (event, closure) in &[
+            ("mousedown", start.as_ref()),
+            ("mousemove", update.as_ref()),
+            ("mouseup", finish.as_ref()),
+            ("dblclick", uncrop.as_ref()),
+        ] {
+            prog.add_event_listener_with_callback(event, closure.unchecked_ref())?;
+        }
+
+        start.forget();
+        update.forget();
+        finish.forget();
+        uncrop.forget();
+
+        for &(button_id, transform) in &[
+            ("#art-params #rotate", Transform::Rotate90),
+            ("#art-params #flip", Transform::FlipHorizontal),
+        ] {
+            let button = query_selector!(self.document > button_id => web_sys::EventTarget)?;
+            let (a, inp) = (adjust.clone(), input.clone());
+            let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+                a.borrow_mut().transforms.push(transform);
+                emit_change(&inp);
+            }) as Box<dyn FnMut(_)>);
+
+            button.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+            closure.forget();
+        }
+
+        // New files fire `input` before `change`, unlike the events we emit.
+        let forget = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            *adjust.borrow_mut() = Adjustments::default();
+        }) as Box<dyn FnMut(_)>);
+        input.add_event_listener_with_callback("input", forget.as_ref().unchecked_ref())?;
+        forget.forget();
+
+        Ok(())
+    }
+
     /// Adds event listener for reading files.
     fn add_file_listener(
         &self,
@@ -256,13 +756,59 @@ impl DomAsciiArtInjector {
         Ok(())
     }
 
+    /// Generates art for all frames of the animation and plays it in the `<pre>` element
+    /// (once the final callback invokes the draw).
+    fn play_animation<U>(
+        anim: AsciiArtAnimation,
+        keeper: &Rc<RefCell<TimingEventKeeper>>,
+        player: &Rc<RefCell<TimingEventKeeper>>,
+        pre: &Rc<web_sys::HtmlPreElement>,
+        step_timeout_ms: u32,
+        final_callback: U,
+    ) where
+        U: FnOnce(Box<dyn FnOnce() + 'static>) -> Result<(), JsValue> + 'static,
+    {
+        pre.set_inner_html(""); // reset <pre> element
+
+        let (pre, player) = (pre.clone(), player.clone());
+        let f = move || {
+            let frames: Vec<_> = anim
+                .generate()
+                .map(|frame| (frame.art.rows.join("\n"), frame.delay_ms))
+                .collect();
+            console_log!("Generated {} frames", frames.len());
+
+            let draw = Box::new(move || {
+                let (mut current, mut elapsed) = (0, 0);
+                pre.set_text_content(Some(&frames[current].0));
+                player.borrow_mut().add_repetitive(
+                    move || {
+                        elapsed += ANIMATION_TICK_MS;
+                        if elapsed < frames[current].1 {
+                            return;
+                        }
+
+                        elapsed = 0;
+                        current = (current + 1) % frames.len();
+                        pre.set_text_content(Some(&frames[current].0));
+                    },
+                    ANIMATION_TICK_MS,
+                );
+            }) as Box<_>;
+
+            final_callback(draw).expect("final callback")
+        };
+
+        keeper.borrow_mut().add(f, step_timeout_ms);
+    }
+
     /// Gets image data from buffer, generates ASCII art and injects into `<pre>` element.
     /// Each step produces an image, steps can be spaced by timeouts, and a callback is
     /// called after each step. Also takes a final callback for invoking the final draw.
     // NOTE: Yes, this is unnecessarily complicated, I know!
     fn inject_from_data_using_document<F, U>(
         gen: Rc<AsciiArtGenerator>,
-        doc: &Rc<web_sys::Document>,
+        art: &Rc<RefCell<Option<AsciiArt>>>,
         keeper: &Rc<RefCell<TimingEventKeeper>>,
         pre: &Rc<web_sys::HtmlPreElement>,
         step_timeout_ms: u32,
@@ -276,40 +822,42 @@ impl DomAsciiArtInjector {
         let delay = Rc::new(Cell::new(step_timeout_ms));
 
         // Callback hell begins!
-        let (pre, doc, inner_d, inner_k) =
-            (pre.clone(), doc.clone(), delay.clone(), keeper.clone());
+        let (pre, art, inner_d, inner_k) =
+            (pre.clone(), art.clone(), delay.clone(), keeper.clone());
         let f = move || {
             let proc = gen.processor();
-            let img = proc.resize();
+            let img = time_stage("resize", || proc.resize());
             callback(&img).expect("queueing resized image");
 
             let (outer_d, outer_k) = (inner_d.clone(), inner_k.clone());
             let f = move || {
                 let proc = gen.processor();
-                let fg = proc.blur_and_invert(&img);
+                let fg = time_stage("blur_and_invert", || proc.blur_and_invert(&img));
                 callback(&fg).expect("queueing blending image");
 
                 let (outer_d, outer_k) = (inner_d.clone(), inner_k.clone());
                 let f = move || {
                     let proc = gen.processor();
-                    let final_img = proc.blend_and_adjust(&img, &fg);
+                    let final_img =
+                        time_stage("blend_and_adjust", || proc.blend_and_adjust(&img, &fg));
                     callback(&final_img).expect("queueing final image");
 
                     let (outer_d, outer_k) = (inner_d.clone(), inner_k.clone());
                     let f = move || {
                         let draw = Box::new(move || {
-                            // Move the timeout keeper inside to prevent clearing all timeouts.
-                            let _keeper = inner_k.clone();
-                            let proc = gen.processor();
-                            for text in proc.generate_from_img(&final_img) {
-                                let div = doc
-                                    .create_element("div")
-                                    .expect("creating art element")
-                                    .dyn_into::<web_sys::HtmlElement>()
-                                    .expect("casting created element");
-                                div.set_inner_text(&text);
-                                pre.append_child(&div).expect("appending div");
-                            }
+                            // Rows are appended a band at a time, and the art is
+                            // stored (for downloads) once they're all in.
+                            let (luma, params) = (final_img.to_luma8(), gen.params());
+                            let done = move |rows| {
+                                *art.borrow_mut() = Some(AsciiArt {
+                                    rows,
+                                    colors: img.to_rgb8(),
+                                    luma,
+                                    params,
+                                });
+                            };
+
+                            Self::append_rows(gen, final_img, pre, inner_k, vec![], done);
                         }) as Box<_>;
 
                         final_callback(draw).expect("final callback")
@@ -332,6 +880,88 @@ impl DomAsciiArtInjector {
 
         keeper.borrow_mut().add(f, delay.get());
     }
+
+    /// Generates the rows for the next band of the final image and appends them to
+    /// the `<pre>` element. The remaining bands are scheduled with the keeper (so that
+    /// the browser gets to draw in between), and the rows are passed on at the end.
+    fn append_rows<F>(
+        gen: Rc<AsciiArtGenerator>,
+        final_img: DynamicImage,
+        pre: Rc<web_sys::HtmlPreElement>,
+        keeper: Rc<RefCell<TimingEventKeeper>>,
+        mut rows: Vec<String>,
+        done: F,
+    ) where
+        F: FnOnce(Vec<String>) + 'static,
+    {
+        let (width, height) = (final_img.width(), final_img.height());
+        let start = rows.len() as u32;
+        let end = cmp::min(start + STREAM_BAND_ROWS, height);
+        let band = final_img.crop_imm(0, start, width, end - start);
+
+        let proc = gen.processor();
+        let doc = pre.owner_document().expect("getting document");
+        let band_rows: Vec<_> = time_stage("generate_from_img", || {
+            proc.generate_from_img(&band).collect()
+        });
+        for text in &band_rows {
+            let div = doc
+                .create_element("div")
+                .expect("creating art element")
+                .dyn_into::<web_sys::HtmlElement>()
+                .expect("casting created element");
+            div.set_inner_text(text);
+            pre.append_child(&div).expect("appending div");
+        }
+
+        rows.extend(band_rows);
+        if end >= height {
+            return done(rows);
+        }
+
+        // Move the timeout keeper inside to prevent clearing all timeouts.
+        let k = keeper.clone();
+        keeper.borrow_mut().add(
+            move || Self::append_rows(gen, final_img, pre, k, rows, done),
+            0,
+        );
+    }
+}
+
+/// Decodes the given (untrusted) image, within the limits for the page. The page
+/// always uses the fast blur, since the art is regenerated as the inputs change.
+fn decode(bytes: &[u8]) -> Result<Decoded, Error> {
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    let decoded = AsciiArtAnimation::decode(bytes, limits, MAX_FRAMES)?;
+    match &decoded {
+        Decoded::Still(gen) => gen.fast_blur.set(true),
+        Decoded::Animated(anim) => anim.fast_blur.set(true),
+    }
+
+    Ok(decoded)
+}
+
+/// Runs a stage of the pipeline, and logs the time it took (if enabled).
+fn time_stage<T, F: FnOnce() -> T>(stage: &str, f: F) -> T {
+    if !LOG_TIMINGS.with(Cell::get) {
+        return f();
+    }
+
+    let Some(perf) = web_sys::window().and_then(|w| w.performance()) else {
+        return f();
+    };
+
+    let start = perf.now();
+    let result = f();
+    console_log!("{}: {:.1} ms", stage, perf.now() - start);
+    result
+}
+
+/// Emits a change event at the input (for regenerating the art).
+fn emit_change(input: &web_sys::HtmlInputElement) {
+    let event = web_sys::Event::new("change").expect("creating event");
+    input.dispatch_event(&event).expect("dispatching event");
 }
 
 /// Abstraction for keeping track of timeouts. This takes `FnOnce` thingies for
@@ -365,10 +995,9 @@ impl TimingEventKeeper {
         let id = crate::set_interval_simple(&f, interval_ms as i32);
         self.stuff.push((id, f, true))
     }
-}
 
-impl Drop for TimingEventKeeper {
-    fn drop(&mut self) {
+    /// Clears all the timeouts and intervals registered so far.
+    pub fn clear(&mut self) {
         self.stuff.drain(..).for_each(|(id, _, repeating)| {
             if repeating {
                 crate::clear_interval(id);
@@ -378,3 +1007,9 @@ impl Drop for TimingEventKeeper {
         });
     }
 }
+
+impl Drop for TimingEventKeeper {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
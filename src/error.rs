@@ -0,0 +1,85 @@
+use crate::art::Crop;
+use image::{ImageError, ImageFormat};
+
+use std::fmt;
+
+/// Errors from loading images for generating the art.
+#[derive(Debug)]
+pub enum Error {
+    /// The image is in a format we can't decode (either unknown, or its
+    /// decoder hasn't been enabled in this build).
+    UnsupportedFormat(Option<ImageFormat>),
+    /// The pixel buffer doesn't match the given dimensions (width, height and
+    /// the actual length of the buffer).
+    BufferSize(u32, u32, usize),
+    /// The crop rectangle is empty or doesn't fit within the image.
+    InvalidCrop(Crop),
+    /// The ramp doesn't have any characters.
+    EmptyRamp,
+    /// The (cropped and transformed) image is empty or exceeds the size limits
+    /// (width and height in pixels).
+    ImageSize(u32, u32),
+    /// The art is empty or exceeds the size limits (columns and rows).
+    ArtSize(u32, u32),
+    /// The animation has more frames than the given maximum.
+    FrameCount(usize),
+    /// Decoder (or some other `image`) error.
+    Image(ImageError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedFormat(format) => {
+                match format {
+                    Some(format) => write!(f, "unsupported image format {:?}", format)?,
+                    None => write!(f, "unknown image format")?,
+                }
+
+                let enabled: Vec<_> = crate::art::SUPPORTED_FORMATS
+                    .iter()
+                    .map(|f| format!("{:?}", f))
+                    .collect();
+                write!(f, " (enabled formats: {})", enabled.join(", "))
+            }
+            Error::BufferSize(w, h, len) => write!(
+                f,
+                "buffer of {} bytes is too small for a {}x{} image",
+                len, w, h
+            ),
+            Error::InvalidCrop(c) => write!(
+                f,
+                "crop rectangle {}x{} at ({}, {}) is empty or outside the image",
+                c.width, c.height, c.x, c.y
+            ),
+            Error::EmptyRamp => write!(f, "ramp must have at least one character"),
+            Error::ImageSize(w, h) => write!(
+                f,
+                "image of {}x{} pixels is empty or exceeds the size limits",
+                w, h
+            ),
+            Error::ArtSize(cols, rows) => write!(
+                f,
+                "art of {} columns and {} rows is empty or exceeds the size limits",
+                cols, rows
+            ),
+            Error::FrameCount(max) => write!(f, "animation has more than {} frames", max),
+            Error::Image(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Self {
+        Error::Image(e)
+    }
+}
@@ -0,0 +1,249 @@
+use crate::art::AsciiArt;
+use image::Rgb;
+
+use std::cmp;
+
+/// mIRC colour palette (codes 0-15).
+const IRC_PALETTE: [[u8; 3]; 16] = [
+    [255, 255, 255],
+    [0, 0, 0],
+    [0, 0, 127],
+    [0, 147, 0],
+    [255, 0, 0],
+    [127, 0, 0],
+    [156, 0, 156],
+    [252, 127, 0],
+    [255, 255, 0],
+    [0, 252, 0],
+    [0, 147, 147],
+    [0, 255, 255],
+    [0, 0, 252],
+    [255, 0, 255],
+    [127, 127, 127],
+    [210, 210, 210],
+];
+
+/// Colours that Discord renders for the ANSI foreground codes 30-37.
+const DISCORD_PALETTE: [[u8; 3]; 8] = [
+    [79, 84, 92],
+    [220, 50, 47],
+    [133, 153, 0],
+    [181, 137, 0],
+    [38, 139, 210],
+    [211, 54, 130],
+    [42, 161, 152],
+    [255, 255, 255],
+];
+
+const IRC_COLOR: char = '\x03';
+const IRC_RESET: char = '\x0f';
+// Toggling bold twice doesn't change anything, but separates the colour code
+// from a comma (which would start a background colour).
+const IRC_SEPARATOR: &str = "\x02\x02";
+
+/// Limits for the art posted in chats (messages usually have size limits).
+#[derive(Clone, Debug, Default)]
+pub struct ChatOptions {
+    /// Maximum number of characters in a line (the rest are cut off).
+    pub max_width: Option<usize>,
+    /// Maximum number of lines (the rest are cut off).
+    pub max_lines: Option<usize>,
+}
+
+impl ChatOptions {
+    /// Returns the rows (and their indices) within the limits.
+    fn rows<'a>(&self, art: &'a AsciiArt) -> impl Iterator<Item = (u32, &'a str)> + 'a {
+        let max_width = self.max_width.unwrap_or(usize::MAX);
+        art.rows
+            .iter()
+            .take(self.max_lines.unwrap_or(usize::MAX))
+            .enumerate()
+            .map(move |(y, row)| {
+                let end = row
+                    .char_indices()
+                    .nth(max_width)
+                    .map_or(row.len(), |(i, _)| i);
+                (y as u32, &row[..end])
+            })
+    }
+}
+
+/// Wraps the art in a Markdown fenced code block.
+pub fn to_markdown(art: &AsciiArt, opts: &ChatOptions) -> String {
+    let rows: Vec<_> = opts.rows(art).map(|(_, row)| row).collect();
+    // The fence should be longer than any run of backticks in the art.
+    let longest_run = rows
+        .iter()
+        .flat_map(|row| row.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(cmp::max(3, longest_run + 1));
+
+    let mut md = format!("{}\n", fence);
+    for row in rows {
+        md.push_str(row);
+        md.push('\n');
+    }
+
+    md.push_str(&fence);
+    md.push('\n');
+    md
+}
+
+/// Writes the art with mIRC colour codes (for IRC bots), using the palette
+/// colours closest to the ones sampled from the image.
+pub fn to_irc(art: &AsciiArt, opts: &ChatOptions) -> String {
+    let mut irc = String::new();
+    for (y, row) in opts.rows(art) {
+        push_color_codes(&mut irc, art, y, row, &IRC_PALETTE, |s, i, next| {
+            // Always use two digits, since the art can have digits.
+            s.push(IRC_COLOR);
+            s.push_str(&format!("{:02}", i));
+            if next == ',' {
+                s.push_str(IRC_SEPARATOR);
+            }
+        });
+
+        irc.push(IRC_RESET);
+        irc.push('\n');
+    }
+
+    irc
+}
+
+/// Wraps the art in an `ansi` code block with the subset of ANSI colours that
+/// Discord renders.
+pub fn to_discord_ansi(art: &AsciiArt, opts: &ChatOptions) -> String {
+    let mut body = String::new();
+    for (y, row) in opts.rows(art) {
+        push_color_codes(&mut body, art, y, row, &DISCORD_PALETTE, |s, i, _| {
+            s.push_str(&format!("\x1b[{}m", 30 + i));
+        });
+
+        body.push_str("\x1b[0m\n");
+    }
+
+    // Backticks can't be escaped in code blocks, so we break their runs with
+    // zero-width spaces to avoid closing the block early.
+    let mut ansi = String::from("```ansi\n");
+    let mut prev = None;
+    for c in body.chars() {
+        if c == '`' && prev == Some('`') {
+            ansi.push('\u{200b}');
+        }
+
+        ansi.push(c);
+        prev = Some(c);
+    }
+
+    ansi.push_str("```\n");
+    ansi
+}
+
+/// Pushes the row, switching to the closest palette colour (using the given
+/// function, which also gets the character that follows) whenever it changes.
+/// Spaces don't have any visible colour, so they don't cause switches.
+fn push_color_codes<F>(
+    s: &mut String,
+    art: &AsciiArt,
+    y: u32,
+    row: &str,
+    palette: &[[u8; 3]],
+    switch: F,
+) where
+    F: Fn(&mut String, usize, char),
+{
+    let mut current = None;
+    for (x, c) in row.chars().enumerate() {
+        let color = match art.colors.get_pixel_checked(x as u32, y) {
+            Some(&p) if c != ' ' => Some(closest_color(p, palette)),
+            _ => None,
+        };
+
+        if let Some(i) = color.filter(|_| color != current) {
+            switch(s, i, c);
+            current = color;
+        }
+
+        s.push(c);
+    }
+}
+
+/// Returns the index of the palette colour closest to the given colour.
+fn closest_color(Rgb(c): Rgb<u8>, palette: &[[u8; 3]]) -> usize {
+    let distance = |p: &[u8; 3]| {
+        (0..3)
+            .map(|i| (c[i] as i32 - p[i] as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::art;
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+
+    #[test]
+    fn fence_is_longer_than_backtick_runs() {
+        let md = to_markdown(&art(&["ab"], |_, _| BLACK), &ChatOptions::default());
+        assert_eq!(md, "```\nab\n```\n");
+
+        let md = to_markdown(
+            &art(&["a````b", "``"], |_, _| BLACK),
+            &ChatOptions::default(),
+        );
+        assert_eq!(md, "`````\na````b\n``\n`````\n");
+    }
+
+    #[test]
+    fn backtick_runs_are_broken_in_discord() {
+        let ansi = to_discord_ansi(&art(&["```", "a`"], |_, _| BLACK), &ChatOptions::default());
+        let body = ansi
+            .strip_prefix("```ansi\n")
+            .and_then(|s| s.strip_suffix("```\n"))
+            .expect("code block");
+        assert!(!body.contains("``"));
+        assert_eq!(
+            body.replace('\u{200b}', ""),
+            "\x1b[30m```\x1b[0m\n\x1b[30ma`\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn rows_are_truncated() {
+        let art = art(&["äbc", "def", "ghi"], |_, _| RED);
+        let opts = ChatOptions {
+            max_width: Some(2),
+            max_lines: Some(2),
+        };
+        assert_eq!(to_markdown(&art, &opts), "```\näb\nde\n```\n");
+        assert_eq!(to_irc(&art, &opts), "\x0304äb\x0f\n\x0304de\x0f\n");
+
+        let opts = ChatOptions {
+            max_width: Some(0),
+            max_lines: Some(1),
+        };
+        assert_eq!(to_markdown(&art, &opts), "```\n\n```\n");
+    }
+
+    #[test]
+    fn irc_colors_are_not_followed_by_commas() {
+        let commas = art(&["a,1 ,2"], |x, _| if x == 0 { RED } else { BLACK });
+        assert_eq!(
+            to_irc(&commas, &ChatOptions::default()),
+            "\x0304a\x0301\x02\x02,1 ,2\x0f\n"
+        );
+
+        // Colours are always written with two digits.
+        let digits = art(&["1"], |_, _| BLACK);
+        assert_eq!(to_irc(&digits, &ChatOptions::default()), "\x03011\x0f\n");
+    }
+}
@@ -0,0 +1,113 @@
+use super::{hex_color, push_color_runs, push_escaped};
+use crate::art::AsciiArt;
+use image::Rgb;
+
+use std::fmt::Write;
+
+/// Options for writing the art as a standalone HTML document.
+#[derive(Clone, Debug)]
+pub struct HtmlOptions {
+    /// Colour of the characters (ignored when `colored` is set).
+    pub foreground: Rgb<u8>,
+    pub background: Rgb<u8>,
+    /// Use the colours sampled from the image for the characters.
+    pub colored: bool,
+    pub title: String,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        HtmlOptions {
+            foreground: Rgb([0, 0, 0]),
+            background: Rgb([255, 255, 255]),
+            colored: false,
+            title: "ASCII Art".into(),
+        }
+    }
+}
+
+/// Writes a self-contained HTML document (with inline CSS) with the art in a `<pre>`
+/// element. The parameters used for generating the art are included in a comment.
+pub fn to_html(art: &AsciiArt, opts: &HtmlOptions) -> String {
+    let p = &art.params;
+    let mut html = String::from("<!DOCTYPE html>\n");
+    let _ = writeln!(
+        html,
+        "<!--\n  Source: {}x{} pixels\n  Art: {}x{} characters\n  \
+         Levels: {}-{}\n  Gamma: {}\n-->",
+        p.source_width,
+        p.source_height,
+        art.colors.width(),
+        art.rows.len(),
+        p.min_level,
+        p.max_level,
+        p.gamma,
+    );
+
+    html.push_str("<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>");
+    opts.title.chars().for_each(|c| push_escaped(&mut html, c));
+    html.push_str("</title>\n");
+    let _ = writeln!(
+        html,
+        "<style>\n\
+         body {{ margin: 0; background: {}; }}\n\
+         pre {{ margin: 1em; color: {}; font-family: monospace, Courier; \
+         font-size: 8px; line-height: 1; }}\n\
+         </style>",
+        hex_color(opts.background),
+        hex_color(opts.foreground),
+    );
+
+    html.push_str("</head>\n<body>\n<pre>");
+    for (y, row) in art.rows.iter().enumerate() {
+        if opts.colored {
+            push_color_runs(
+                &mut html,
+                art,
+                y as u32,
+                row,
+                |s, c| {
+                    let _ = write!(s, "<span style=\"color: {}\">", hex_color(c));
+                },
+                "</span>",
+            );
+        } else {
+            row.chars().for_each(|c| push_escaped(&mut html, c));
+        }
+
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::art;
+
+    #[test]
+    fn title_and_art_are_escaped() {
+        let opts = HtmlOptions {
+            title: "<script>&".into(),
+            ..HtmlOptions::default()
+        };
+        let html = to_html(&art(&["</pre>", "'\""], |_, _| Rgb([0, 0, 0])), &opts);
+        assert!(html.contains("<title>&lt;script&gt;&amp;</title>"));
+        assert!(html.contains("<pre>&lt;/pre&gt;\n&#39;&quot;\n</pre>"));
+    }
+
+    #[test]
+    fn colors_are_grouped_in_runs() {
+        let art = art(&["a b>"], |x, _| Rgb([0, 0, (x / 3) as u8 * 255]));
+        let opts = HtmlOptions {
+            colored: true,
+            ..HtmlOptions::default()
+        };
+        assert!(to_html(&art, &opts).contains(
+            "<pre><span style=\"color: #000000\">a b</span>\
+             <span style=\"color: #0000ff\">&gt;</span>\n</pre>"
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::art::{ArtParams, AsciiArt};
+use serde::Serialize;
+
+/// Structured form of the art, for downstream tooling.
+#[derive(Serialize)]
+struct Document<'a> {
+    /// Number of columns and rows in the grid.
+    width: u32,
+    height: u32,
+    /// Characters used (from densest to lightest).
+    ramp: String,
+    params: &'a ArtParams,
+    /// Rows of cells.
+    cells: Vec<Vec<Cell>>,
+}
+
+#[derive(Serialize)]
+struct Cell {
+    char: char,
+    luma: u8,
+    rgb: [u8; 3],
+}
+
+impl<'a> Document<'a> {
+    fn new(art: &'a AsciiArt) -> Self {
+        let cells = art
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .map(|(x, c)| {
+                        let (x, y) = (x as u32, y as u32);
+                        Cell {
+                            char: c,
+                            luma: art.luma.get_pixel_checked(x, y).map_or(0, |p| p.0[0]),
+                            rgb: art.colors.get_pixel_checked(x, y).map_or([0; 3], |p| p.0),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Document {
+            width: art.luma.width(),
+            height: art.luma.height(),
+            ramp: art.params.ramp.clone(),
+            params: &art.params,
+            cells,
+        }
+    }
+}
+
+/// Writes the art as JSON, with the grid dimensions, ramp, parameters and
+/// the character, luma and RGB values of each cell.
+#[cfg(feature = "json")]
+pub fn to_json(art: &AsciiArt) -> String {
+    // Serializing to memory can't fail for these types.
+    serde_json::to_string(&Document::new(art)).expect("serializing art")
+}
+
+/// Writes the art as MessagePack (with the same structure as JSON).
+#[cfg(feature = "msgpack")]
+pub fn to_msgpack(art: &AsciiArt) -> Vec<u8> {
+    rmp_serde::to_vec_named(&Document::new(art)).expect("serializing art")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::art;
+    use image::{Luma, Rgb};
+
+    fn checked_art() -> AsciiArt {
+        let mut art = art(&["#.", ".#"], |x, y| Rgb([x as u8, y as u8, 7]));
+        art.luma.put_pixel(1, 0, Luma([200]));
+        art
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_has_the_cells() {
+        let json = to_json(&checked_art());
+        let doc: serde_json::Value = serde_json::from_str(&json).expect("parsing JSON");
+        assert_eq!(
+            (doc["width"].as_u64(), doc["height"].as_u64()),
+            (Some(2), Some(2))
+        );
+        assert_eq!(doc["params"]["cols"], 2);
+        assert_eq!(
+            doc["cells"][0][1],
+            serde_json::json!({ "char": ".", "luma": 200, "rgb": [1, 0, 7] })
+        );
+        assert_eq!(doc["cells"][1][0]["char"], ".");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_has_the_cells() {
+        #[derive(serde::Deserialize)]
+        struct Doc {
+            width: u32,
+            height: u32,
+            cells: Vec<Vec<Cell>>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Cell {
+            char: char,
+            luma: u8,
+            rgb: [u8; 3],
+        }
+
+        let doc: Doc = rmp_serde::from_slice(&to_msgpack(&checked_art())).expect("parsing");
+        assert_eq!((doc.width, doc.height), (2, 2));
+        let cells: Vec<_> = doc
+            .cells
+            .iter()
+            .flatten()
+            .map(|c| (c.char, c.luma, c.rgb))
+            .collect();
+        assert_eq!(
+            cells,
+            [
+                ('#', 0, [0, 0, 7]),
+                ('.', 200, [1, 0, 7]),
+                ('.', 0, [0, 1, 7]),
+                ('#', 0, [1, 1, 7])
+            ]
+        );
+    }
+}
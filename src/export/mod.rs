@@ -0,0 +1,89 @@
+//! Exporters for the generated art.
+
+mod chat;
+mod font;
+mod html;
+#[cfg(any(feature = "json", feature = "msgpack"))]
+mod json;
+mod png;
+mod svg;
+
+pub use self::chat::{to_discord_ansi, to_irc, to_markdown, ChatOptions};
+pub use self::html::{to_html, HtmlOptions};
+#[cfg(feature = "json")]
+pub use self::json::to_json;
+#[cfg(feature = "msgpack")]
+pub use self::json::to_msgpack;
+pub use self::png::{render_image, to_png, PngOptions};
+pub use self::svg::{to_svg, SvgOptions};
+
+use crate::art::AsciiArt;
+use image::Rgb;
+
+/// Formats the colour in `#rrggbb` notation.
+fn hex_color(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Pushes the character to the string, escaping it for XML (and HTML).
+fn push_escaped(s: &mut String, c: char) {
+    match c {
+        '&' => s.push_str("&amp;"),
+        '<' => s.push_str("&lt;"),
+        '>' => s.push_str("&gt;"),
+        '"' => s.push_str("&quot;"),
+        '\'' => s.push_str("&#39;"),
+        _ => s.push(c),
+    }
+}
+
+/// Pushes the (escaped) characters of the row, grouping the ones with the same
+/// colour into runs, which are opened using the given function and closed with
+/// the given tag. Spaces don't have any visible colour, so they don't break runs.
+fn push_color_runs<F>(s: &mut String, art: &AsciiArt, y: u32, row: &str, open: F, close: &str)
+where
+    F: Fn(&mut String, Rgb<u8>),
+{
+    let mut current = None;
+    for (x, c) in row.chars().enumerate() {
+        let color = art.colors.get_pixel_checked(x as u32, y).copied();
+        if c != ' ' && color != current {
+            if current.is_some() {
+                s.push_str(close);
+            }
+
+            if let Some(color) = color {
+                open(s, color);
+            }
+
+            current = color;
+        }
+
+        push_escaped(s, c);
+    }
+
+    if current.is_some() {
+        s.push_str(close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::art::{AsciiArt, AsciiArtGenerator};
+    use image::{GrayImage, Rgb, RgbImage};
+
+    /// Creates art with the given rows, using the function for colouring the characters.
+    pub(super) fn art<F: Fn(u32, u32) -> Rgb<u8>>(rows: &[&str], color: F) -> AsciiArt {
+        let cols = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as u32;
+        let gen = AsciiArtGenerator::from_luma8(1, 1, &[0]).expect("creating generator");
+        let mut params = gen.params();
+        params.cols = cols;
+        params.rows = rows.len() as u32;
+        AsciiArt {
+            rows: rows.iter().map(|r| r.to_string()).collect(),
+            colors: RgbImage::from_fn(cols, rows.len() as u32, color),
+            luma: GrayImage::new(cols, rows.len() as u32),
+            params,
+        }
+    }
+}
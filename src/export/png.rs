@@ -0,0 +1,146 @@
+use super::font::{self, GLYPH_SIZE};
+use crate::art::{AsciiArt, DEFAULT_CHAR_HEIGHT, DEFAULT_CHAR_WIDTH};
+use crate::error::Error;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, Rgb, RgbImage};
+
+// Glyphs are square, so we add some line spacing to match the character
+// aspect ratio assumed while resizing the image.
+const CELL_WIDTH: u32 = GLYPH_SIZE;
+const CELL_HEIGHT: u32 =
+    (GLYPH_SIZE as f32 * DEFAULT_CHAR_HEIGHT / DEFAULT_CHAR_WIDTH + 0.5) as u32;
+const GLYPH_OFFSET_Y: u32 = (CELL_HEIGHT - GLYPH_SIZE) / 2;
+// Maximum number of pixels in the rasterized art (~768 MiB for RGB).
+const MAX_PIXELS: u64 = 1 << 28;
+
+/// Options for rasterizing the art.
+#[derive(Clone, Debug)]
+pub struct PngOptions {
+    /// Colour of the characters (ignored when `colored` is set).
+    pub foreground: Rgb<u8>,
+    pub background: Rgb<u8>,
+    /// Padding (in pixels) around the art.
+    pub padding: u32,
+    /// Use the colours sampled from the image for the characters.
+    pub colored: bool,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            foreground: Rgb([0, 0, 0]),
+            background: Rgb([255, 255, 255]),
+            padding: 10,
+            colored: false,
+        }
+    }
+}
+
+/// Rasterizes the art using the embedded bitmap font. Fails with
+/// [`Error::ArtSize`] if the image would be too large.
+pub fn render_image(art: &AsciiArt, opts: &PngOptions) -> Result<RgbImage, Error> {
+    let cols = art
+        .rows
+        .iter()
+        .map(|r| r.chars().count())
+        .max()
+        .unwrap_or(0);
+    let (cols, rows) = (
+        u32::try_from(cols).unwrap_or(u32::MAX),
+        u32::try_from(art.rows.len()).unwrap_or(u32::MAX),
+    );
+    let side = |n: u32, cell: u32| {
+        n.checked_mul(cell)?
+            .checked_add(opts.padding.checked_mul(2)?)
+    };
+    let (w, h) = match (side(cols, CELL_WIDTH), side(rows, CELL_HEIGHT)) {
+        (Some(w), Some(h)) if u64::from(w) * u64::from(h) <= MAX_PIXELS => (w, h),
+        _ => return Err(Error::ArtSize(cols, rows)),
+    };
+
+    let mut img = RgbImage::from_pixel(w, h, opts.background);
+    for (y, row) in art.rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            // These can't overflow, since the cells are within the image.
+            let (x, y) = (x as u32, y as u32);
+            let color = match art.colors.get_pixel_checked(x, y) {
+                Some(&p) if opts.colored => p,
+                _ => opts.foreground,
+            };
+
+            let (left, top) = (
+                opts.padding + x * CELL_WIDTH,
+                opts.padding + y * CELL_HEIGHT + GLYPH_OFFSET_Y,
+            );
+            for (dy, bits) in font::glyph(c).iter().enumerate() {
+                for dx in 0..GLYPH_SIZE {
+                    if bits & (1 << dx) != 0 {
+                        img.put_pixel(left + dx, top + dy as u32, color);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(img)
+}
+
+/// Rasterizes the art and encodes it as PNG.
+pub fn to_png(art: &AsciiArt, opts: &PngOptions) -> Result<Vec<u8>, Error> {
+    let img = render_image(art, opts)?;
+    let mut bytes = vec![];
+    PngEncoder::new(&mut bytes).write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ExtendedColorType::Rgb8,
+    )?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::art;
+
+    #[test]
+    fn image_fits_the_cells() {
+        let art = art(&["#.", "."], |_, _| Rgb([255, 0, 0]));
+        let img = render_image(&art, &PngOptions::default()).expect("rendering art");
+        assert_eq!(
+            img.dimensions(),
+            (2 * CELL_WIDTH + 20, 2 * CELL_HEIGHT + 20)
+        );
+
+        let colored = PngOptions {
+            colored: true,
+            padding: 0,
+            ..PngOptions::default()
+        };
+        let img = render_image(&art, &colored).expect("rendering art");
+        assert!(img.pixels().any(|p| *p == Rgb([255, 0, 0])));
+        assert!(!img.pixels().any(|p| *p == Rgb([0, 0, 0])));
+    }
+
+    #[test]
+    fn large_images_are_rejected() {
+        let small = art(&["#"], |_, _| Rgb([0, 0, 0]));
+        let padded = PngOptions {
+            padding: u32::MAX / 2,
+            ..PngOptions::default()
+        };
+        match render_image(&small, &padded) {
+            Err(Error::ArtSize(1, 1)) => (),
+            r => panic!("expected art size error, got {:?}", r.err()),
+        }
+
+        // This doesn't overflow, but has too many pixels.
+        let row = "#".repeat(1 << 16);
+        let wide = art(&[row.as_str(); 64], |_, _| Rgb([0, 0, 0]));
+        match to_png(&wide, &PngOptions::default()) {
+            Err(Error::ArtSize(cols, 64)) => assert_eq!(cols, 1 << 16),
+            r => panic!("expected art size error, got {:?}", r.err()),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::{hex_color, push_color_runs, push_escaped};
+use crate::art::{AsciiArt, DEFAULT_CHAR_HEIGHT, DEFAULT_CHAR_WIDTH};
+use image::Rgb;
+
+use std::fmt::Write;
+
+// Monospace glyphs are usually 0.6em wide, which gives us the character width.
+const FONT_SIZE: f32 = DEFAULT_CHAR_WIDTH / 0.6;
+// Distance of the baseline from the top of the character cell.
+const BASELINE: f32 = DEFAULT_CHAR_HEIGHT * 0.8;
+
+/// Options for writing the art as SVG.
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+    /// Colour of the characters (ignored when `colored` is set).
+    pub foreground: Rgb<u8>,
+    /// Background colour (transparent if unset).
+    pub background: Option<Rgb<u8>>,
+    /// Use the colours sampled from the image for the characters.
+    pub colored: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            foreground: Rgb([0, 0, 0]),
+            background: Some(Rgb([255, 255, 255])),
+            colored: false,
+        }
+    }
+}
+
+/// Writes the art as SVG, with one `<text>` per row (and `<tspan>` runs for colours).
+/// The view box is based on the character cell, so that the art scales nicely.
+pub fn to_svg(art: &AsciiArt, opts: &SvgOptions) -> String {
+    let cols = art
+        .rows
+        .iter()
+        .map(|r| r.chars().count())
+        .max()
+        .unwrap_or(0);
+    let (w, h) = (
+        cols as f32 * DEFAULT_CHAR_WIDTH,
+        art.rows.len() as f32 * DEFAULT_CHAR_HEIGHT,
+    );
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" \
+         font-family=\"monospace\" font-size=\"{}\" fill=\"{}\" xml:space=\"preserve\">",
+        w,
+        h,
+        FONT_SIZE,
+        hex_color(opts.foreground),
+    );
+
+    if let Some(bg) = opts.background {
+        let _ = writeln!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+            hex_color(bg)
+        );
+    }
+
+    for (y, row) in art.rows.iter().enumerate() {
+        // Stretch the rows to exactly fill the cells, regardless of the font.
+        let _ = write!(
+            svg,
+            "<text x=\"0\" y=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\">",
+            y as f32 * DEFAULT_CHAR_HEIGHT + BASELINE,
+            row.chars().count() as f32 * DEFAULT_CHAR_WIDTH,
+        );
+
+        if opts.colored {
+            push_color_runs(
+                &mut svg,
+                art,
+                y as u32,
+                row,
+                |s, c| {
+                    let _ = write!(s, "<tspan fill=\"{}\">", hex_color(c));
+                },
+                "</tspan>",
+            );
+        } else {
+            row.chars().for_each(|c| push_escaped(&mut svg, c));
+        }
+
+        svg.push_str("</text>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::art;
+
+    #[test]
+    fn characters_are_escaped() {
+        let svg = to_svg(
+            &art(&["<&>\"'"], |_, _| Rgb([0, 0, 0])),
+            &SvgOptions::default(),
+        );
+        assert!(svg.contains(">&lt;&amp;&gt;&quot;&#39;</text>"));
+        assert!(!svg.contains("<&"));
+    }
+
+    #[test]
+    fn colors_are_grouped_in_runs() {
+        let art = art(&["ab c", "<"], |x, _| Rgb([x as u8, 0, 0]));
+        let opts = SvgOptions {
+            background: None,
+            colored: true,
+            ..SvgOptions::default()
+        };
+        let svg = to_svg(&art, &opts);
+        assert!(!svg.contains("<rect"));
+        assert!(svg.contains(
+            "<tspan fill=\"#000000\">a</tspan><tspan fill=\"#010000\">b </tspan>\
+             <tspan fill=\"#030000\">c</tspan></text>"
+        ));
+        assert!(svg.contains("<tspan fill=\"#000000\">&lt;</tspan></text>"));
+    }
+}
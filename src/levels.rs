@@ -0,0 +1,263 @@
+//! Levels (and gamma) adjustment for the blended image, and luma conversion.
+//!
+//! With the `simd` feature, pixels are processed with `std::simd` (in chunks of
+//! [`LANES`] pixels, which ends up using `simd128` in wasm builds with that target
+//! feature), and the rest go through the scalar version, which gives the same output.
+
+#[cfg(feature = "simd")]
+use std::simd::cmp::SimdOrd;
+#[cfg(feature = "simd")]
+use std::simd::num::{SimdFloat, SimdUint};
+#[cfg(feature = "simd")]
+use std::simd::Simd;
+
+// Blending two 8-bit values (equally) gives one of these many sums.
+const BLEND_SUMS: usize = 2 * 255 + 1;
+// sRGB coefficients for luma (same as `image`, but in integers, so that the
+// result doesn't depend on the platform).
+const LUMA: [u32; 3] = [2126, 7152, 722];
+const LUMA_DIV: u32 = 10000;
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+/// Lookup table for the levels and gamma curve.
+///
+/// The levels are adjusted on the value (the brightest channel, in HSV) of the
+/// blended pixel, keeping the hue and saturation, which is the same as scaling
+/// all channels by the same factor. So, the table has the factor for each
+/// possible value (i.e., the sum of two channels).
+pub(crate) struct Levels {
+    scale: [f32; BLEND_SUMS],
+}
+
+impl Levels {
+    pub(crate) fn new(min_level: u8, max_level: u8, gamma: f32) -> Self {
+        let (min, max, inv_gamma) = (
+            min_level as f32 / 255.0,
+            max_level as f32 / 255.0,
+            1.0 / gamma,
+        );
+        let mut scale = [0.0; BLEND_SUMS];
+        // Black stays black, and the rest are scaled to get the adjusted value.
+        for (sum, s) in scale.iter_mut().enumerate().skip(1) {
+            let v = sum as f32 / (BLEND_SUMS - 1) as f32;
+            let adjusted = if v <= min {
+                0.0
+            } else if v >= max {
+                1.0
+            } else {
+                ((v - min) / (max - min)).powf(inv_gamma)
+            };
+
+            *s = 255.0 * adjusted / sum as f32;
+        }
+
+        Levels { scale }
+    }
+
+    /// Blends the foreground into the image (both RGB) and adjusts the levels in place.
+    pub(crate) fn apply(&self, buf: &mut [u8], fg: &[u8]) {
+        #[cfg(feature = "simd")]
+        let (buf, fg) = {
+            let split = buf.len() / (3 * LANES) * 3 * LANES;
+            let (head, tail) = buf.split_at_mut(split);
+            self.apply_simd(head, &fg[..split]);
+            (tail, &fg[split..])
+        };
+
+        self.apply_scalar(buf, fg);
+    }
+
+    fn apply_scalar(&self, buf: &mut [u8], fg: &[u8]) {
+        for (p, f) in buf.chunks_exact_mut(3).zip(fg.chunks_exact(3)) {
+            let sums = [
+                p[0] as u16 + f[0] as u16,
+                p[1] as u16 + f[1] as u16,
+                p[2] as u16 + f[2] as u16,
+            ];
+            let scale = self.scale[sums[0].max(sums[1]).max(sums[2]) as usize];
+            for (c, sum) in p.iter_mut().zip(sums) {
+                *c = (sum as f32 * scale + 0.5) as u8;
+            }
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    fn apply_simd(&self, buf: &mut [u8], fg: &[u8]) {
+        for (p, f) in buf
+            .chunks_exact_mut(3 * LANES)
+            .zip(fg.chunks_exact(3 * LANES))
+        {
+            let sums = |c: usize| -> Simd<u32, LANES> {
+                Simd::from_array(std::array::from_fn(|i| {
+                    p[3 * i + c] as u32 + f[3 * i + c] as u32
+                }))
+            };
+
+            let (r, g, b) = (sums(0), sums(1), sums(2));
+            let max = r.simd_max(g).simd_max(b);
+            let scale = Simd::gather_or_default(&self.scale, max.cast());
+            let adjust = |sum: Simd<u32, LANES>| {
+                (sum.cast::<f32>() * scale + Simd::splat(0.5))
+                    .cast::<u8>()
+                    .to_array()
+            };
+
+            let (r, g, b) = (adjust(r), adjust(g), adjust(b));
+            for (i, pixel) in p.chunks_exact_mut(3).enumerate() {
+                pixel.copy_from_slice(&[r[i], g[i], b[i]]);
+            }
+        }
+    }
+}
+
+/// Converts RGB pixels to luma (rounded to the nearest value).
+pub(crate) fn rgb_to_luma(luma: &mut [u8], rgb: &[u8]) {
+    #[cfg(feature = "simd")]
+    let (luma, rgb) = {
+        let split = luma.len() / LANES * LANES;
+        let (head, tail) = luma.split_at_mut(split);
+        rgb_to_luma_simd(head, &rgb[..3 * split]);
+        (tail, &rgb[3 * split..])
+    };
+
+    rgb_to_luma_scalar(luma, rgb);
+}
+
+fn rgb_to_luma_scalar(luma: &mut [u8], rgb: &[u8]) {
+    for (l, p) in luma.iter_mut().zip(rgb.chunks_exact(3)) {
+        let sum = LUMA[0] * p[0] as u32 + LUMA[1] * p[1] as u32 + LUMA[2] * p[2] as u32;
+        *l = ((sum + LUMA_DIV / 2) / LUMA_DIV) as u8;
+    }
+}
+
+#[cfg(feature = "simd")]
+fn rgb_to_luma_simd(luma: &mut [u8], rgb: &[u8]) {
+    for (l, p) in luma
+        .chunks_exact_mut(LANES)
+        .zip(rgb.chunks_exact(3 * LANES))
+    {
+        let channel = |c: usize| -> Simd<u32, LANES> {
+            Simd::from_array(std::array::from_fn(|i| p[3 * i + c] as u32))
+        };
+
+        let sum = Simd::splat(LUMA_DIV / 2)
+            + Simd::splat(LUMA[0]) * channel(0)
+            + Simd::splat(LUMA[1]) * channel(1)
+            + Simd::splat(LUMA[2]) * channel(2);
+        l.copy_from_slice(&(sum / Simd::splat(LUMA_DIV)).cast::<u8>().to_array());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Default levels, along with some extremes.
+    const PARAMS: &[(u8, u8, f32)] = &[
+        (78, 125, 0.78),
+        (0, 255, 1.0),
+        (10, 240, 0.1),
+        (10, 240, 5.0),
+        (0, 0, 1.0),
+        (255, 255, 0.78),
+        (200, 50, 0.78),
+    ];
+
+    /// Every pair of values in the first channel (and something else in the others),
+    /// with some pixels left over for the scalar version.
+    fn pixels() -> (Vec<u8>, Vec<u8>) {
+        let (mut buf, mut fg) = (vec![], vec![]);
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                buf.extend([x, y, x.wrapping_mul(7).wrapping_add(y)]);
+                fg.extend([y, x.wrapping_add(y), x ^ y]);
+            }
+        }
+
+        buf.extend([1, 2, 3, 4, 5, 6]);
+        fg.extend([7, 8, 9, 10, 11, 12]);
+        (buf, fg)
+    }
+
+    /// Adjusts the levels the long way around. Scaling the channels changes the
+    /// value (in HSV) without changing the hue and saturation.
+    fn reference(levels: (u8, u8, f32), p: &[u8], f: &[u8]) -> [u8; 3] {
+        let (min, max, inv_gamma) = (
+            levels.0 as f64 / 255.0,
+            levels.1 as f64 / 255.0,
+            1.0 / levels.2 as f64,
+        );
+        let rgb = [0, 1, 2].map(|c| (p[c] as f64 + f[c] as f64) / 510.0);
+        let v = rgb[0].max(rgb[1]).max(rgb[2]);
+        let adjusted = if v <= min {
+            0.0
+        } else if v >= max {
+            1.0
+        } else {
+            ((v - min) / (max - min)).powf(inv_gamma)
+        };
+
+        // Black stays black (it's always at or below the minimum).
+        let scale = if v > 0.0 { adjusted / v } else { 0.0 };
+        rgb.map(|c| (c * scale * 255.0).round() as u8)
+    }
+
+    #[test]
+    fn levels_match_hsv_adjustment() {
+        let (buf, fg) = pixels();
+        for &params in PARAMS {
+            let mut adjusted = buf.clone();
+            Levels::new(params.0, params.1, params.2).apply(&mut adjusted, &fg);
+            for ((a, p), f) in adjusted
+                .chunks_exact(3)
+                .zip(buf.chunks_exact(3))
+                .zip(fg.chunks_exact(3))
+            {
+                let expected = reference(params, p, f);
+                // Rounding can differ (slightly), since this takes a different route.
+                assert!(
+                    a.iter().zip(expected).all(|(&a, e)| a.abs_diff(e) <= 1),
+                    "{:?} + {:?} with {:?}: {:?} (expected {:?})",
+                    p,
+                    f,
+                    params,
+                    a,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn luma_matches_image() {
+        let (buf, _) = pixels();
+        let img = image::RgbImage::from_raw(buf.len() as u32 / 3, 1, buf.clone()).unwrap();
+        let mut luma = vec![0; buf.len() / 3];
+        rgb_to_luma(&mut luma, &buf);
+        // `image` uses floats (which can differ in rounding).
+        let expected = image::DynamicImage::ImageRgb8(img).to_luma8();
+        for (i, (&l, &e)) in luma.iter().zip(expected.as_raw()).enumerate() {
+            let p = &buf[3 * i..3 * i + 3];
+            assert!(l.abs_diff(e) <= 1, "{:?}: {} (expected {})", p, l, e);
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn simd_matches_scalar() {
+        let (buf, fg) = pixels();
+        for &params in PARAMS {
+            let levels = Levels::new(params.0, params.1, params.2);
+            let (mut simd, mut scalar) = (buf.clone(), buf.clone());
+            levels.apply(&mut simd, &fg);
+            levels.apply_scalar(&mut scalar, &fg);
+            assert_eq!(simd, scalar, "levels {:?}", params);
+        }
+
+        let (mut simd, mut scalar) = (vec![0; buf.len() / 3], vec![0; buf.len() / 3]);
+        rgb_to_luma(&mut simd, &buf);
+        rgb_to_luma_scalar(&mut scalar, &buf);
+        assert_eq!(simd, scalar);
+    }
+}
@@ -1,4 +1,5 @@
 #![feature(cell_update, const_fn_floating_point_arithmetic)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
 
 use wasm_bindgen::prelude::*;
 
@@ -30,22 +31,39 @@ macro_rules! query_selector {
     };
 }
 
+mod anim;
 mod art;
+mod blur;
 mod dom;
+mod error;
+pub mod export;
+mod levels;
+mod limits;
+mod transform;
 mod utils;
 include!(concat!(env!("OUT_DIR"), "/demo_output.rs"));
 
-pub use self::art::AsciiArtGenerator;
+pub use self::anim::{AsciiArtAnimation, AsciiArtFrame, Decoded};
+pub use self::art::{ArtParams, ArtStream, AsciiArt, AsciiArtGenerator, Crop, SUPPORTED_FORMATS};
 pub use self::dom::{DomAsciiArtInjector, TimingEventKeeper};
+pub use self::error::Error;
+pub use self::limits::{SizeLimits, SizePolicy};
+pub use self::transform::Transform;
+pub use image::imageops::FilterType;
 
 use self::art::{DEFAULT_GAMMA, DEFAULT_MAX_LEVEL, DEFAULT_MIN_LEVEL};
 
+const WEBCAM_FPS: u32 = 10;
+// Fill colour for the corners of rotated images (same as the page background).
+const DEFAULT_FILL: image::Rgb<u8> = image::Rgb([255, 255, 255]);
+
 #[wasm_bindgen]
 pub fn start() -> Result<(), JsValue> {
     utils::set_panic_hook();
     let injector = DomAsciiArtInjector::init();
     let search_str = injector.window.location().search()?;
     let params = web_sys::UrlSearchParams::new_with_str(&search_str)?;
+    injector.set_log_timings(params.has("timings"));
     let content = query_selector!(injector.document > ".outline" => web_sys::Element)?;
 
     if let Some(url) = params.get("url") {
@@ -58,6 +76,8 @@ pub fn start() -> Result<(), JsValue> {
             params.get("max").and_then(|v| v.parse().ok()),
             params.get("gamma").and_then(|v| v.parse().ok()),
             params.get("width").and_then(|v| v.parse().ok()),
+            params.get("crop").and_then(|v| v.parse().ok()),
+            url_transforms(&params),
             50,
             |draw: Box<dyn FnOnce() + 'static>| {
                 draw();
@@ -67,12 +87,28 @@ pub fn start() -> Result<(), JsValue> {
         );
     }
 
+    if params.get("webcam").is_some() {
+        content.class_list().add_1("remove")?;
+
+        return injector.inject_from_webcam(
+            "art-box",
+            params.get("min").and_then(|v| v.parse().ok()),
+            params.get("max").and_then(|v| v.parse().ok()),
+            params.get("gamma").and_then(|v| v.parse().ok()),
+            params.get("width").and_then(|v| v.parse().ok()),
+            params
+                .get("fps")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(WEBCAM_FPS),
+        );
+    }
+
     injector.inject_from_data("header-box", &DEMO_DATA)?;
     display_success(&injector.document)?;
 
     let (k, o) = (injector.keeper.clone(), content.clone());
-    // Currently, image resizing takes an awful lot of time for huge images.
-    // `image` doesn't use SIMD, and we can't use rayon in wasm.
+    // Huge images are box-sampled before the final resize, since `image`
+    // doesn't use SIMD, and we can't use rayon in wasm.
     injector.inject_on_file_loads(
         "file-thingy",  // input element
         "art-box",      // art <pre> element
@@ -99,9 +135,37 @@ pub fn start() -> Result<(), JsValue> {
         },
     )?;
 
+    injector.add_download_button("download-png", "ascii-art.png", "image/png", |art| {
+        export::to_png(art, &export::PngOptions::default())
+    })?;
+    injector.add_download_button("download-html", "ascii-art.html", "text/html", |art| {
+        Ok(export::to_html(art, &export::HtmlOptions::default()).into_bytes())
+    })?;
+
     set_listeners(&injector.document, content)
 }
 
+/// Generates the art from RGBA pixels (like canvas `ImageData`) and returns the rows
+/// separated by newlines. Levels and gamma default to the page's defaults when unset.
+#[wasm_bindgen(js_name = generateFromPixels)]
+pub fn generate_from_pixels(
+    pixels: wasm_bindgen::Clamped<Vec<u8>>,
+    width: u32,
+    height: u32,
+    min: Option<u8>,
+    max: Option<u8>,
+    gamma: Option<f32>,
+) -> Result<String, JsValue> {
+    let gen = AsciiArtGenerator::from_rgba8(width, height, &pixels)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    gen.min_level.set(min.unwrap_or(DEFAULT_MIN_LEVEL));
+    gen.max_level.set(max.unwrap_or(DEFAULT_MAX_LEVEL));
+    gen.gamma.set(gamma.unwrap_or(DEFAULT_GAMMA));
+    gen.fast_blur.set(true);
+
+    Ok(gen.processor().generate().rows.join("\n"))
+}
+
 // FIXME: Need to clean this up!
 
 fn set_listeners(
@@ -211,6 +275,32 @@ fn set_listeners(
     Ok(())
 }
 
+/// Transforms from the URL parameters - `rotate` (in degrees, clockwise) with
+/// an optional `fill` colour (`RRGGBB`, white by default), and `flip` (`h`, `v` or `hv`).
+fn url_transforms(params: &web_sys::UrlSearchParams) -> Vec<Transform> {
+    let fill = params
+        .get("fill")
+        .and_then(|v| u32::from_str_radix(v.trim_start_matches('#'), 16).ok())
+        .map_or(DEFAULT_FILL, |c| {
+            image::Rgb([(c >> 16) as u8, (c >> 8) as u8, c as u8])
+        });
+
+    let mut transforms: Vec<_> = params
+        .get("rotate")
+        .and_then(|v| v.parse().ok())
+        .and_then(|degrees| Transform::rotation(degrees, fill))
+        .into_iter()
+        .collect();
+
+    let flip = params.get("flip").unwrap_or_default();
+    transforms.extend(
+        flip.chars()
+            .filter_map(|c| c.to_string().parse::<Transform>().ok()),
+    );
+
+    transforms
+}
+
 fn display_success(doc: &web_sys::Document) -> Result<(), JsValue> {
     let banner = query_selector!(doc > ".success-banner" => web_sys::Element)?;
     let list = banner.class_list();
@@ -0,0 +1,51 @@
+/// What to do when the size of the art falls outside the limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizePolicy {
+    /// Scale the art down to fit within the maximum size (maintaining aspect
+    /// ratio), and stretch it to at least one column and row.
+    #[default]
+    Clamp,
+    /// Fail with [`Error::ArtSize`](crate::Error::ArtSize) (and leave the size as it was).
+    Reject,
+}
+
+/// Limits for the dimensions of the source image and the art.
+///
+/// Empty images (zero width or height) are always rejected, and the art always
+/// has at least one column and row. Everything else is unlimited by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SizeLimits {
+    /// Maximum width of the source image (in pixels, after cropping and transforms).
+    pub max_source_width: Option<u32>,
+    /// Maximum height of the source image (in pixels, after cropping and transforms).
+    pub max_source_height: Option<u32>,
+    /// Maximum number of pixels in the source image (after cropping and transforms).
+    pub max_source_pixels: Option<u64>,
+    /// Maximum number of columns in the art.
+    pub max_cols: Option<u32>,
+    /// Maximum number of rows in the art.
+    pub max_rows: Option<u32>,
+    /// What to do when the art is empty or larger than the maximum size.
+    pub policy: SizePolicy,
+}
+
+impl SizeLimits {
+    /// Checks whether an image of the given size can be used for the art.
+    pub fn allows_source(&self, (width, height): (u32, u32)) -> bool {
+        width > 0
+            && height > 0
+            && self.max_source_width.is_none_or(|w| width <= w)
+            && self.max_source_height.is_none_or(|h| height <= h)
+            && self
+                .max_source_pixels
+                .is_none_or(|p| width as u64 * height as u64 <= p)
+    }
+
+    /// Checks whether the art can have the given columns and rows.
+    pub fn allows_grid(&self, (cols, rows): (u32, u32)) -> bool {
+        cols > 0
+            && rows > 0
+            && self.max_cols.is_none_or(|c| cols <= c)
+            && self.max_rows.is_none_or(|r| rows <= r)
+    }
+}
@@ -0,0 +1,287 @@
+use image::imageops;
+use image::{DynamicImage, Rgb, Rgba, RgbaImage};
+
+use std::str::FromStr;
+
+/// Transforms for the (cropped) image, applied before resizing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Transform {
+    /// Rotate by 90 degrees (clockwise).
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Rotate by an arbitrary angle (in degrees, clockwise). The image is expanded
+    /// to fit the rotated image, and the corners are filled with the given colour.
+    Rotate {
+        degrees: f32,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_rgb"))]
+        fill: Rgb<u8>,
+    },
+    /// Mirror the image (flip left to right).
+    FlipHorizontal,
+    /// Flip the image upside down.
+    FlipVertical,
+}
+
+impl Transform {
+    /// Returns the transform for rotating by the given angle (in degrees), using
+    /// the lossless rotations whenever possible. Full turns are ignored.
+    pub fn rotation(degrees: f32, fill: Rgb<u8>) -> Option<Self> {
+        match degrees.rem_euclid(360.0) {
+            0.0 => None,
+            90.0 => Some(Transform::Rotate90),
+            180.0 => Some(Transform::Rotate180),
+            270.0 => Some(Transform::Rotate270),
+            d => Some(Transform::Rotate { degrees: d, fill }),
+        }
+    }
+
+    /// Applies this transform to the image.
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        match *self {
+            Transform::Rotate90 => img.rotate90(),
+            Transform::Rotate180 => img.rotate180(),
+            Transform::Rotate270 => img.rotate270(),
+            Transform::Rotate { degrees, fill } => {
+                DynamicImage::ImageRgba8(rotate(&img.to_rgba8(), degrees, fill))
+            }
+            Transform::FlipHorizontal => img.fliph(),
+            Transform::FlipVertical => img.flipv(),
+        }
+    }
+
+    /// Returns the dimensions of an image of the given size after this transform.
+    pub fn size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match *self {
+            Transform::Rotate90 | Transform::Rotate270 => (height, width),
+            Transform::Rotate { degrees, .. } => {
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                let (w, h) = (width as f32, height as f32);
+                (
+                    (w * cos.abs() + h * sin.abs()).round() as u32,
+                    (w * sin.abs() + h * cos.abs()).round() as u32,
+                )
+            }
+            _ => (width, height),
+        }
+    }
+
+    /// Maps a point in the transformed image back to the original image (both
+    /// normalized to [0, 1]). Only possible for the lossless transforms.
+    pub(crate) fn unmap(&self, (x, y): (f32, f32)) -> Option<(f32, f32)> {
+        match *self {
+            Transform::Rotate90 => Some((y, 1.0 - x)),
+            Transform::Rotate180 => Some((1.0 - x, 1.0 - y)),
+            Transform::Rotate270 => Some((1.0 - y, x)),
+            Transform::Rotate { .. } => None,
+            Transform::FlipHorizontal => Some((1.0 - x, y)),
+            Transform::FlipVertical => Some((x, 1.0 - y)),
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = ();
+
+    /// Parses the flips (`h` and `v`) and the lossless rotations (`90`, `180`
+    /// and `270`). Arbitrary rotations need a fill colour (see [`Transform::rotation`]).
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "h" | "horizontal" => Ok(Transform::FlipHorizontal),
+            "v" | "vertical" => Ok(Transform::FlipVertical),
+            "90" => Ok(Transform::Rotate90),
+            "180" => Ok(Transform::Rotate180),
+            "270" => Ok(Transform::Rotate270),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Serializes the fill colour as `[r, g, b]`.
+#[cfg(feature = "serde")]
+fn serialize_rgb<S: serde::Serializer>(rgb: &Rgb<u8>, s: S) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&rgb.0, s)
+}
+
+/// Rotates the image about its center (expanding it to fit the corners) and
+/// fills the uncovered regions with the given colour.
+fn rotate(img: &RgbaImage, degrees: f32, fill: Rgb<u8>) -> RgbaImage {
+    let (w, h) = Transform::Rotate { degrees, fill }.size(img.dimensions());
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (src_cx, src_cy) = (
+        (img.width() as f32 - 1.0) / 2.0,
+        (img.height() as f32 - 1.0) / 2.0,
+    );
+    let (cx, cy) = ((w as f32 - 1.0) / 2.0, (h as f32 - 1.0) / 2.0);
+    let fill = Rgba([fill[0], fill[1], fill[2], 255]);
+
+    RgbaImage::from_fn(w, h, |x, y| {
+        // Rotate backwards to find where this pixel came from.
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let (sx, sy) = (dx * cos + dy * sin + src_cx, -dx * sin + dy * cos + src_cy);
+        imageops::interpolate_bilinear(img, sx, sy).unwrap_or(fill)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::art::{AsciiArtGenerator, Crop};
+    use image::{GrayImage, Luma, RgbImage};
+
+    const LOSSLESS: &[Transform] = &[
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipHorizontal,
+        Transform::FlipVertical,
+    ];
+
+    /// Image where each pixel has its own value (its index).
+    fn numbered(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            Luma([(y * width + x) as u8])
+        }))
+    }
+
+    /// Checks that unmapping the center of each pixel of the transformed image
+    /// (through all the transforms) lands on the pixel it came from.
+    fn check_unmap(src: &GrayImage, out: &GrayImage, transforms: &[Transform]) {
+        let (w, h) = out.dimensions();
+        for (x, y, p) in out.enumerate_pixels() {
+            let center = ((x as f32 + 0.5) / w as f32, (y as f32 + 0.5) / h as f32);
+            let (ux, uy) = transforms
+                .iter()
+                .rev()
+                .try_fold(center, |p, t| t.unmap(p))
+                .unwrap();
+            let (sx, sy) = (
+                (ux * src.width() as f32) as u32,
+                (uy * src.height() as f32) as u32,
+            );
+            assert_eq!(
+                src.get_pixel(sx, sy),
+                p,
+                "{:?} at {}x{} (unmapped to {}x{})",
+                transforms,
+                x,
+                y,
+                sx,
+                sy
+            );
+        }
+    }
+
+    #[test]
+    fn lossless_transforms_move_pixels() {
+        // 0 1 2
+        // 3 4 5
+        let img = numbered(3, 2);
+        let expected: &[(Transform, &[&[u8]])] = &[
+            (Transform::Rotate90, &[&[3, 0], &[4, 1], &[5, 2]]),
+            (Transform::Rotate180, &[&[5, 4, 3], &[2, 1, 0]]),
+            (Transform::Rotate270, &[&[2, 5], &[1, 4], &[0, 3]]),
+            (Transform::FlipHorizontal, &[&[2, 1, 0], &[5, 4, 3]]),
+            (Transform::FlipVertical, &[&[3, 4, 5], &[0, 1, 2]]),
+        ];
+
+        for (t, rows) in expected {
+            let out = t.apply(&img).to_luma8();
+            let actual: Vec<Vec<u8>> = out.rows().map(|r| r.map(|p| p.0[0]).collect()).collect();
+            assert_eq!(actual, rows.to_vec(), "{:?}", t);
+            assert_eq!(out.dimensions(), t.size((3, 2)), "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn unmapping_finds_the_source_pixel() {
+        let img = numbered(4, 3);
+        let src = img.to_luma8();
+        for &t in LOSSLESS {
+            check_unmap(&src, &t.apply(&img).to_luma8(), &[t]);
+        }
+
+        let chains: &[&[Transform]] = &[
+            &[Transform::Rotate90, Transform::FlipHorizontal],
+            &[Transform::FlipVertical, Transform::Rotate270],
+            &[
+                Transform::Rotate90,
+                Transform::Rotate90,
+                Transform::Rotate180,
+            ],
+            &[
+                Transform::Rotate270,
+                Transform::FlipVertical,
+                Transform::Rotate90,
+            ],
+        ];
+
+        for &chain in chains {
+            let out = chain.iter().fold(img.clone(), |img, t| t.apply(&img));
+            let size = chain.iter().fold((4, 3), |size, t| t.size(size));
+            assert_eq!(out.width(), size.0);
+            assert_eq!(out.height(), size.1);
+            check_unmap(&src, &out.to_luma8(), chain);
+        }
+    }
+
+    #[test]
+    fn transforms_apply_after_cropping() {
+        let img = numbered(6, 5);
+        let crop = Crop {
+            x: 1,
+            y: 2,
+            width: 3,
+            height: 2,
+        };
+        let cropped = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+
+        let mut gen = AsciiArtGenerator::from_image(img).unwrap();
+        gen.set_crop(Some(crop)).unwrap();
+        for &t in LOSSLESS {
+            gen.set_transforms(vec![t]).unwrap();
+            let out = gen.processor().source().to_luma8();
+            assert_eq!(out.dimensions(), t.size((3, 2)), "{:?}", t);
+            // Unmapped points are relative to the cropped image.
+            check_unmap(&cropped.to_luma8(), &out, &[t]);
+        }
+    }
+
+    #[test]
+    fn arbitrary_rotations_fill_the_corners() {
+        let (blue, red) = (Rgb([0, 0, 255]), Rgb([255, 0, 0]));
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(20, 10, blue));
+        let t = Transform::Rotate {
+            degrees: 45.0,
+            fill: red,
+        };
+
+        let out = t.apply(&img).to_rgb8();
+        assert_eq!(out.dimensions(), (21, 21));
+        assert_eq!(out.dimensions(), t.size((20, 10)));
+        for (x, y) in [(0, 0), (20, 0), (0, 20), (20, 20)] {
+            assert_eq!(*out.get_pixel(x, y), red, "corner at {}x{}", x, y);
+        }
+
+        assert_eq!(*out.get_pixel(10, 10), blue);
+        assert_eq!(t.unmap((0.5, 0.5)), None);
+    }
+
+    #[test]
+    fn rotations_prefer_lossless_transforms() {
+        let fill = Rgb([255, 255, 255]);
+        assert_eq!(Transform::rotation(90.0, fill), Some(Transform::Rotate90));
+        assert_eq!(Transform::rotation(-90.0, fill), Some(Transform::Rotate270));
+        assert_eq!(Transform::rotation(540.0, fill), Some(Transform::Rotate180));
+        assert_eq!(Transform::rotation(360.0, fill), None);
+        assert_eq!(
+            Transform::rotation(30.0, fill),
+            Some(Transform::Rotate {
+                degrees: 30.0,
+                fill
+            })
+        );
+    }
+}
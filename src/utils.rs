@@ -12,71 +12,60 @@ pub(crate) fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
-/* RGB <-> HSV conversion impl based on Python `colorsys` module. */
+/* Per-pixel and per-row loops (parallel with the `parallel` feature, natively). */
 
-/// Converts an RGB pixel value in [0, 1] to HSV.
-pub(crate) fn convert_rgb_to_hsv((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
-    let max = max(r, max(g, b));
-    let min = min(r, min(g, b));
-    let v = max;
-    if min == max {
-        return (0.0, 0.0, v);
-    }
+// Pixels per block (for splitting the image between threads).
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+const BLOCK_PIXELS: usize = 4096;
 
-    let s = (max - min) / max;
-    let r = (max - r) / (max - min);
-    let g = (max - g) / (max - min);
-    let b = (max - b) / (max - min);
-    let h = if r == max {
-        b - g
-    } else if g == max {
-        2.0 + r - b
-    } else {
-        4.0 + g - r
-    };
+/// Calls the closure for blocks of pixels of the image (with the corresponding
+/// pixels of the other image), given the raw buffers and their number of channels.
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+pub(crate) fn zip_pixels_mut<F>(
+    buf: &mut [u8],
+    buf_channels: usize,
+    other: &[u8],
+    other_channels: usize,
+    f: F,
+) where
+    F: Fn(&mut [u8], &[u8]) + Send + Sync,
+{
+    use rayon::prelude::*;
 
-    return (h / 6.0, s, v);
+    buf.par_chunks_mut(BLOCK_PIXELS * buf_channels)
+        .zip(other.par_chunks(BLOCK_PIXELS * other_channels))
+        .for_each(|(b, o)| f(b, o));
 }
 
-/// Converts a HSV pixel value to RGB (in range [0, 1]).
-pub(crate) fn convert_hsv_to_rgb((h, s, v): (f32, f32, f32)) -> (f32, f32, f32) {
-    if s == 0.0 {
-        return (v, v, v);
-    }
-
-    let i = (h * 6.0) as u8;
-    let f = (h * 6.0) - (h * 6.0).floor();
-    let p = v * (1.0 - s);
-    let q = v * (1.0 - s * f);
-    let t = v * (1.0 - s * (1.0 - f));
-
-    match i % 6 {
-        0 => (v, t, p),
-        1 => (q, v, p),
-        2 => (p, v, t),
-        3 => (p, q, v),
-        4 => (t, p, v),
-        5 => (v, p, q),
-        _ => unreachable!("bleh?!?@!"),
-    }
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+pub(crate) fn zip_pixels_mut<F>(buf: &mut [u8], _: usize, other: &[u8], _: usize, f: F)
+where
+    F: Fn(&mut [u8], &[u8]),
+{
+    f(buf, other)
 }
 
-/* min/max workaround for floats */
+/// Maps each row (given the number of rows). In parallel, the rows are collected
+/// before they're returned.
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+pub(crate) fn map_rows<T, F>(rows: u32, f: F) -> impl Iterator<Item = T>
+where
+    T: Send,
+    F: Fn(u32) -> T + Send + Sync,
+{
+    use rayon::prelude::*;
 
-#[inline]
-fn max(v1: f32, v2: f32) -> f32 {
-    if v1 > v2 {
-        v1
-    } else {
-        v2
-    }
+    (0..rows)
+        .into_par_iter()
+        .map(f)
+        .collect::<Vec<_>>()
+        .into_iter()
 }
 
-#[inline]
-fn min(v1: f32, v2: f32) -> f32 {
-    if v1 < v2 {
-        v1
-    } else {
-        v2
-    }
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+pub(crate) fn map_rows<T, F>(rows: u32, f: F) -> impl Iterator<Item = T>
+where
+    F: Fn(u32) -> T,
+{
+    (0..rows).map(f)
 }
@@ -0,0 +1,67 @@
+//! Decoding animations within the limits.
+#![cfg(feature = "gif")]
+
+use charcoal::{AsciiArtAnimation, Decoded, Error};
+use image::codecs::gif::GifEncoder;
+use image::error::LimitErrorKind;
+use image::{Delay, Frame, ImageError, Limits, Rgba, RgbaImage};
+
+/// Encodes a GIF with the given number of frames (of different shades).
+fn gif(frames: u8) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut encoder = GifEncoder::new(&mut bytes);
+    encoder
+        .encode_frames((0..frames).map(|i| {
+            let img = RgbaImage::from_pixel(16, 8, Rgba([i * 20, 0, 0, 255]));
+            Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(50, 1))
+        }))
+        .expect("encoding gif");
+    drop(encoder);
+    bytes
+}
+
+#[test]
+fn animations_are_decoded_in_one_pass() {
+    match AsciiArtAnimation::decode(&gif(5), Limits::default(), 5) {
+        Ok(Decoded::Animated(anim)) => assert_eq!(anim.len(), 5),
+        Ok(Decoded::Still(_)) => panic!("expected an animation"),
+        Err(e) => panic!("decoding animation: {}", e),
+    }
+}
+
+#[test]
+fn single_frames_are_still() {
+    let png = include_bytes!("fixtures/checker.png");
+    for bytes in [gif(1), png.to_vec()] {
+        match AsciiArtAnimation::decode(&bytes, Limits::default(), 5) {
+            Ok(Decoded::Still(_)) => (),
+            Ok(Decoded::Animated(_)) => panic!("expected a still image"),
+            Err(e) => panic!("decoding image: {}", e),
+        }
+    }
+
+    let anim = AsciiArtAnimation::from_bytes_with_limits(png, Limits::default(), 1)
+        .expect("decoding still image");
+    assert_eq!(anim.len(), 1);
+}
+
+#[test]
+fn frames_are_limited() {
+    match AsciiArtAnimation::from_bytes_with_limits(&gif(5), Limits::default(), 4) {
+        Err(Error::FrameCount(4)) => (),
+        r => panic!("expected frame count error, got {:?}", r.err()),
+    }
+}
+
+#[test]
+fn frames_share_the_allocation_limit() {
+    // Each frame is 16x8 RGBA (512 bytes), so a single one fits, but not all of them.
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(2048);
+    match AsciiArtAnimation::from_bytes_with_limits(&gif(5), limits, 10) {
+        Err(Error::Image(ImageError::Limits(e))) => {
+            assert!(matches!(e.kind(), LimitErrorKind::InsufficientMemory))
+        }
+        r => panic!("expected limit error, got {:?}", r.err()),
+    }
+}
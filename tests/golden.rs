@@ -0,0 +1,218 @@
+//! Golden-file tests for each stage of the pipeline.
+//!
+//! The output of every stage is compared against the text files in `tests/golden`.
+//! When the output changes intentionally, run `BLESS=1 cargo test --test golden`
+//! to update them (and review the diff before committing).
+//!
+//! The goldens use the (default) exact Gaussian blur, and the art is also checked
+//! against the art from the fast blur, which should look the same.
+
+use charcoal::AsciiArtGenerator;
+use image::DynamicImage;
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Runs the image through the pipeline (resizing it to the given width, if any)
+/// and checks the output of each stage.
+fn check_pipeline(name: &str, image_path: &str, width: Option<u32>) {
+    let gen = load_fixture(image_path, width);
+    let proc = gen.processor();
+    let img = proc.resize();
+    let fg = proc.blur_and_invert(&img);
+    let final_img = proc.blend_and_adjust(&img, &fg);
+    let rows: Vec<_> = proc.generate_from_img(&final_img).collect();
+
+    let stages = [
+        ("resize", dump_image(&img)),
+        ("blur", dump_image(&fg)),
+        ("blend", dump_image(&final_img)),
+        ("art", rows.join("\n") + "\n"),
+    ];
+
+    let bless = std::env::var_os("BLESS").is_some();
+    let failures: Vec<_> = stages
+        .iter()
+        .filter_map(|(stage, actual)| {
+            let path = golden_path(name, stage);
+            if bless {
+                fs::create_dir_all(path.parent().unwrap()).expect("creating golden directory");
+                fs::write(&path, actual).expect("writing golden file");
+                return None;
+            }
+
+            match fs::read_to_string(&path) {
+                Ok(expected) if expected == *actual => None,
+                Ok(expected) => Some(format!(
+                    "{}: {}",
+                    path.display(),
+                    first_difference(&expected, actual)
+                )),
+                Err(_) => Some(format!("{}: missing golden file", path.display())),
+            }
+        })
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "output doesn't match (run with BLESS=1 to update if this is intended):\n{}",
+        failures.join("\n")
+    );
+}
+
+fn load_fixture(image_path: &str, width: Option<u32>) -> AsciiArtGenerator {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let bytes = fs::read(root.join(image_path)).expect("reading fixture");
+    let mut gen = AsciiArtGenerator::from_bytes(&bytes).expect("loading fixture");
+    if let Some(w) = width {
+        gen.set_width(w).expect("resizing fixture");
+    }
+
+    gen
+}
+
+/// Runs the image through the pipeline with both the fast and the exact blur,
+/// and checks that they're close enough (the levels amplify the differences in
+/// the blur, but the characters should only be off by a few places in the ramp).
+fn check_blur_parity(image_path: &str, width: Option<u32>) {
+    let gen = load_fixture(image_path, width);
+    let run = |fast| {
+        gen.fast_blur.set(fast);
+        let proc = gen.processor();
+        let img = proc.resize();
+        let fg = proc.blur_and_invert(&img);
+        let final_img = proc.blend_and_adjust(&img, &fg);
+        let rows: String = proc.generate_from_img(&final_img).collect();
+        (
+            fg.to_rgb8().into_raw(),
+            final_img.to_luma8().into_raw(),
+            rows,
+        )
+    };
+
+    let (fast, exact) = (run(true), run(false));
+    let max_diff = |a: &[u8], b: &[u8]| a.iter().zip(b).map(|(x, y)| x.abs_diff(*y)).max();
+    let mean_diff = |a: &[u8], b: &[u8]| {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| x.abs_diff(*y) as f32)
+            .sum::<f32>()
+            / a.len() as f32
+    };
+
+    assert!(
+        max_diff(&fast.0, &exact.0) <= Some(2),
+        "{}: blur differs",
+        image_path
+    );
+    assert!(
+        max_diff(&fast.1, &exact.1) <= Some(8),
+        "{}: blend differs",
+        image_path
+    );
+    assert!(
+        mean_diff(&fast.1, &exact.1) < 0.5,
+        "{}: blend differs",
+        image_path
+    );
+
+    let ramp: Vec<_> = gen.params().ramp.chars().collect();
+    let index = |c| {
+        ramp.iter()
+            .position(|&r| r == c)
+            .expect("character in ramp")
+    };
+    for (i, (f, e)) in fast.2.chars().zip(exact.2.chars()).enumerate() {
+        assert!(
+            index(f).abs_diff(index(e)) <= 3,
+            "{}: character {} is {:?} (expected {:?})",
+            image_path,
+            i,
+            f,
+            e
+        );
+    }
+}
+
+fn golden_path(name: &str, stage: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.{}.txt", name, stage))
+}
+
+/// Writes the dimensions and color type, followed by the pixels as hex (one row per line).
+fn dump_image(img: &DynamicImage) -> String {
+    let channels = img.color().channel_count() as usize;
+    let mut s = format!("{}x{} {:?}\n", img.width(), img.height(), img.color());
+    for row in img.as_bytes().chunks(img.width() as usize * channels) {
+        for (i, pixel) in row.chunks(channels).enumerate() {
+            if i > 0 {
+                s.push(' ');
+            }
+
+            for c in pixel {
+                write!(s, "{:02x}", c).unwrap();
+            }
+        }
+
+        s.push('\n');
+    }
+
+    s
+}
+
+fn first_difference(expected: &str, actual: &str) -> String {
+    let (mut expected_lines, mut actual_lines) = (expected.lines(), actual.lines());
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (None, None) => break,
+            (e, a) => {
+                return format!(
+                    "line {} differs\n  expected: {}\n  actual:   {}",
+                    line,
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+
+    "line endings differ".into()
+}
+
+#[test]
+fn demo() {
+    check_pipeline("demo", "config/demo.png", Some(120));
+}
+
+#[test]
+fn gradient() {
+    check_pipeline("gradient", "tests/fixtures/gradient.png", None);
+}
+
+#[test]
+fn checker() {
+    check_pipeline("checker", "tests/fixtures/checker.png", None);
+}
+
+#[test]
+fn disc() {
+    check_pipeline("disc", "tests/fixtures/disc.png", Some(32));
+}
+
+#[test]
+fn tiny() {
+    check_pipeline("tiny", "tests/fixtures/tiny.png", None);
+}
+
+#[test]
+fn fast_blur_matches_exact() {
+    check_blur_parity("config/demo.png", Some(120));
+    check_blur_parity("config/demo.png", Some(400));
+    check_blur_parity("tests/fixtures/gradient.png", None);
+    check_blur_parity("tests/fixtures/checker.png", None);
+    check_blur_parity("tests/fixtures/disc.png", Some(32));
+    check_blur_parity("tests/fixtures/tiny.png", None);
+}
@@ -0,0 +1,218 @@
+//! Edge cases for the dimensions of the source image and the art.
+
+use charcoal::{AsciiArtGenerator, Error, SizeLimits, SizePolicy, Transform};
+use image::Rgb;
+
+fn generator(width: u32, height: u32) -> AsciiArtGenerator {
+    let pixels = vec![128; (width * height) as usize];
+    AsciiArtGenerator::from_luma8(width, height, &pixels).expect("creating generator")
+}
+
+/// Runs the whole pipeline and checks that the art matches the grid size.
+fn check_art(gen: &AsciiArtGenerator) {
+    let (cols, rows) = gen.grid_size();
+    let art = gen.processor().generate();
+    assert_eq!(art.rows.len(), rows as usize);
+    assert!(art.rows.iter().all(|r| r.chars().count() == cols as usize));
+}
+
+#[test]
+fn empty_images_are_rejected() {
+    for (w, h) in [(0, 0), (0, 10), (10, 0)] {
+        match AsciiArtGenerator::from_luma8(w, h, &[]) {
+            Err(Error::ImageSize(ew, eh)) => assert_eq!((ew, eh), (w, h)),
+            r => panic!("expected size error for {}x{}, got {:?}", w, h, r.err()),
+        }
+    }
+}
+
+#[test]
+fn thin_images_have_at_least_one_row() {
+    for (w, h) in [(1, 1), (300, 1), (20_000, 1), (1, 20_000)] {
+        let gen = generator(w, h);
+        let (cols, rows) = gen.grid_size();
+        assert!(cols > 0 && rows > 0, "{}x{} gives {}x{}", w, h, cols, rows);
+        check_art(&gen);
+    }
+}
+
+#[test]
+fn tiny_sizes_are_clamped_by_default() {
+    let mut gen = generator(100, 10);
+    assert_eq!(gen.set_width(0).unwrap(), (1, 1));
+    assert_eq!(gen.set_width(3).unwrap(), (3, 1));
+    assert_eq!(gen.set_rows(0).unwrap().1, 1);
+    assert_eq!(gen.fit(0, 0).unwrap(), (1, 1));
+    check_art(&gen);
+}
+
+#[test]
+fn upscaling_is_bounded_by_the_limits() {
+    let mut gen = generator(100, 50);
+    assert_eq!(gen.set_width(400).unwrap().0, 400);
+    // Nothing's capped by default.
+    assert_eq!(gen.set_width(2000).unwrap(), (2000, 545));
+
+    gen.set_size_limits(SizeLimits {
+        max_cols: Some(1000),
+        max_rows: Some(1000),
+        ..SizeLimits::default()
+    })
+    .unwrap();
+    assert_eq!(gen.set_width(1_000_000).unwrap(), (1000, 272));
+    assert_eq!(gen.set_rows(1_000_000).unwrap(), (1000, 272));
+    assert_eq!(gen.fit(1_000_000, 1_000_000).unwrap(), (1000, 272));
+
+    // Thin images are bounded by the rows instead.
+    let mut gen = generator(1, 100);
+    gen.set_size_limits(SizeLimits {
+        max_rows: Some(100),
+        ..SizeLimits::default()
+    })
+    .unwrap();
+    let (cols, rows) = gen.set_width(500).unwrap();
+    assert!(cols <= 2 && rows == 100, "got {}x{}", cols, rows);
+    check_art(&gen);
+}
+
+#[test]
+fn large_sizes_are_clamped_to_the_limits() {
+    let mut gen = generator(200, 100);
+    gen.set_size_limits(SizeLimits {
+        max_cols: Some(80),
+        max_rows: Some(20),
+        ..SizeLimits::default()
+    })
+    .unwrap();
+
+    let (cols, rows) = gen.set_width(1000).unwrap();
+    assert!(cols <= 80 && rows <= 20, "got {}x{}", cols, rows);
+    // Aspect ratio is maintained (the rows are the limiting factor here).
+    assert_eq!(rows, 20);
+    check_art(&gen);
+}
+
+#[test]
+fn sizes_outside_the_limits_are_rejected() {
+    let mut gen = generator(200, 100);
+    gen.set_width(50).unwrap();
+    let size = gen
+        .set_size_limits(SizeLimits {
+            max_cols: Some(100),
+            policy: SizePolicy::Reject,
+            ..SizeLimits::default()
+        })
+        .unwrap();
+
+    for width in [0, 1, 101] {
+        match gen.set_width(width) {
+            Err(Error::ArtSize(..)) => (),
+            r => panic!("expected size error for width {}, got {:?}", width, r),
+        }
+
+        assert_eq!(gen.grid_size(), size, "size shouldn't change on errors");
+    }
+
+    assert_eq!(gen.set_width(100).unwrap(), (100, 27));
+}
+
+#[test]
+fn tall_thin_sources_are_limited() {
+    let mut gen = generator(1, 8192);
+    let limits = SizeLimits {
+        max_cols: Some(500),
+        max_rows: Some(1000),
+        policy: SizePolicy::Reject,
+        ..SizeLimits::default()
+    };
+
+    // The default size is already too tall.
+    assert!(matches!(
+        gen.set_size_limits(limits),
+        Err(Error::ArtSize(1, 4468))
+    ));
+
+    let clamp = SizeLimits {
+        policy: SizePolicy::Clamp,
+        ..limits
+    };
+    let size = gen.set_size_limits(clamp).unwrap();
+    assert!(size.0 >= 1 && size.1 <= 1000, "got {:?}", size);
+
+    // Wide art from a thin image is too tall (rather than millions of rows).
+    gen.set_size_limits(limits).unwrap();
+    for width in [2, 500] {
+        match gen.set_width(width) {
+            Err(Error::ArtSize(cols, rows)) => assert!(cols <= width && rows > 1000),
+            r => panic!("expected size error for width {}, got {:?}", width, r),
+        }
+
+        assert_eq!(gen.grid_size(), size, "size shouldn't change on errors");
+    }
+
+    check_art(&gen);
+}
+
+#[test]
+fn current_size_is_checked_against_new_limits() {
+    let mut gen = generator(200, 100);
+    let limits = SizeLimits {
+        max_cols: Some(50),
+        policy: SizePolicy::Reject,
+        ..SizeLimits::default()
+    };
+
+    assert!(matches!(
+        gen.set_size_limits(limits),
+        Err(Error::ArtSize(200, 54))
+    ));
+    assert_eq!(gen.size_limits(), SizeLimits::default());
+
+    let limits = SizeLimits {
+        policy: SizePolicy::Clamp,
+        ..limits
+    };
+    assert_eq!(gen.set_size_limits(limits).unwrap(), (50, 13));
+}
+
+#[test]
+fn source_limits() {
+    let mut gen = generator(200, 100);
+    let limits = SizeLimits {
+        max_source_pixels: Some(10_000),
+        ..SizeLimits::default()
+    };
+    assert!(matches!(
+        gen.set_size_limits(limits),
+        Err(Error::ImageSize(200, 100))
+    ));
+
+    let limits = SizeLimits {
+        max_source_width: Some(150),
+        ..SizeLimits::default()
+    };
+    let size = gen.set_transforms(vec![Transform::Rotate90]).unwrap();
+    assert_eq!(gen.set_size_limits(limits).unwrap(), size);
+    // Without the rotation, the image is too wide.
+    assert!(matches!(
+        gen.set_transforms(vec![]),
+        Err(Error::ImageSize(200, 100))
+    ));
+    assert_eq!(gen.transforms(), &[Transform::Rotate90]);
+}
+
+#[test]
+fn degenerate_rotations_are_rejected() {
+    let mut gen = generator(20, 10);
+    let fill = Rgb([255, 255, 255]);
+    for degrees in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        let t = Transform::Rotate { degrees, fill };
+        assert!(matches!(
+            gen.set_transforms(vec![t]),
+            Err(Error::ImageSize(0, 0))
+        ));
+    }
+
+    assert!(gen.transforms().is_empty());
+    check_art(&gen);
+}
@@ -0,0 +1,76 @@
+//! Streamed art should be the same as the art generated in one go.
+
+use charcoal::AsciiArtGenerator;
+
+/// Tall image (with colourful stripes and some noise), resized to the given rows.
+fn generator(rows: u32) -> AsciiArtGenerator {
+    let (width, height) = (40, 1000);
+    let mut seed = 0x9e37_79b9_u32;
+    let pixels: Vec<_> = (0..width * height)
+        .flat_map(|i| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let (x, y) = (i % width, i / width);
+            let stripe = if (y / 50) % 2 == 0 { 200 } else { 30 };
+            [stripe, (x * 6) as u8, (seed % 256) as u8]
+        })
+        .collect();
+
+    let mut gen = AsciiArtGenerator::from_rgb8(width, height, &pixels).expect("creating generator");
+    gen.set_rows(rows).expect("resizing");
+    gen
+}
+
+#[test]
+fn streamed_rows_match() {
+    // Fewer rows than a band, and a few bands (with a partial one at the end).
+    for rows in [10, 150, 333] {
+        let gen = generator(rows);
+        for fast in [true, false] {
+            gen.fast_blur.set(fast);
+            let expected = gen.processor().generate().rows;
+            let streamed: Vec<_> = gen.processor().stream().collect();
+            assert_eq!(streamed.len(), rows as usize);
+            assert!(
+                streamed == expected,
+                "streamed art differs for {} rows (fast blur: {})",
+                rows,
+                fast
+            );
+        }
+    }
+}
+
+#[test]
+fn streamed_bands_cover_the_image() {
+    let gen = generator(150);
+    let mut stream = gen.processor().stream();
+    assert_eq!(stream.image().height(), 150);
+
+    let mut bands = vec![];
+    while let Some(band) = stream.next_band() {
+        bands.push(band.len());
+    }
+
+    assert!(bands.len() > 1, "expected multiple bands, got {:?}", bands);
+    assert_eq!(bands.iter().sum::<usize>(), 150);
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn streams_to_writer() {
+    let gen = generator(100);
+    let mut stream = gen.processor().stream();
+    // Rows which have already been taken aren't written again.
+    let first = stream.next().expect("first row");
+
+    let mut out = vec![];
+    stream.write_to(&mut out).expect("writing to buffer");
+    let expected = gen.processor().generate().rows;
+    assert_eq!(first, expected[0]);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        expected[1..].join("\n") + "\n"
+    );
+}